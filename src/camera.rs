@@ -0,0 +1,199 @@
+//! This module maps points of the flame to pixels of the picture
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use super::options::RenderOptions;
+use super::point::Point;
+
+/// Which edge of the picture spans the [-1, 1] range of the flame. The other edge follows the
+/// aspect ratio of the picture, so that the flame is never stretched.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Fit {
+    /// The whole [-1, 1]x[-1, 1] square is visible, with extra room along the longer edge
+    ShorterEdge,
+    /// The picture is filled with the middle of the [-1, 1]x[-1, 1] square, cropping it along
+    /// the shorter edge
+    LongerEdge,
+}
+
+impl Fit {
+    /// Returns the name of the fit, as written in flam3 files and given on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Fit::ShorterEdge => "shorter",
+            Fit::LongerEdge => "longer",
+        }
+    }
+
+    /// Returns the fit of a name
+    pub fn from_name(name: &str) -> Option<Fit> {
+        match name {
+            "shorter" => Some(Fit::ShorterEdge),
+            "longer" => Some(Fit::LongerEdge),
+            _ => None,
+        }
+    }
+}
+
+/// Visible rectangle of the flame
+#[derive(Debug, Clone)]
+pub struct Camera {
+    width: u32,
+    height: u32,
+    center: Point,
+    /// Number of pixels per unit of the flame
+    pixels_per_unit: f32,
+    /// Cosine and sine of the rotation of the camera
+    cos: f32,
+    sin: f32,
+}
+
+impl Camera {
+    /// Creates the camera described by the center, scale, zoom and rotation of `options`,
+    /// looking at the accumulation grid
+    pub fn new(options: &RenderOptions) -> Camera {
+        let (sin, cos) = libm::sincosf(options.rotate.to_radians());
+        let (width, height) = options.supersampled_size();
+        Camera {
+            width,
+            height,
+            center: Point(options.center_x, options.center_y),
+            pixels_per_unit: pixels_per_unit(options) * options.supersample as f32,
+            cos,
+            sin,
+        }
+    }
+
+    /// Returns the (row, column) of the pixel a point falls in, or `None` when the point is
+    /// outside of the picture. x selects the column and y the row.
+    pub fn pixel(&self, coord: &Point) -> Option<(u32, u32)> {
+        let dx = coord.0 - self.center.0;
+        let dy = coord.1 - self.center.1;
+        // Turning the camera is the same as turning the flame the other way
+        let x = self.cos * dx + self.sin * dy;
+        let y = -self.sin * dx + self.cos * dy;
+        let column = (x * self.pixels_per_unit + self.width as f32 / 2.0).floor();
+        let row = (y * self.pixels_per_unit + self.height as f32 / 2.0).floor();
+        // NaN fails both comparisons and is discarded too
+        if column >= 0.0 && column < self.width as f32 && row >= 0.0 && row < self.height as f32 {
+            Some((row as u32, column as u32))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the index of the pixel a point falls in, row by row, or `None` when the point is
+    /// outside of the picture
+    pub fn index(&self, coord: &Point) -> Option<usize> {
+        self.pixel(coord)
+            .map(|(row, column)| (row * self.width + column) as usize)
+    }
+}
+
+/// Returns the number of pixels per unit of the flame when the scale is 1 and the zoom is 0
+pub fn fit_pixels_per_unit(width: u32, height: u32, fit: Fit) -> f32 {
+    let edge = match fit {
+        Fit::ShorterEdge => width.min(height),
+        Fit::LongerEdge => width.max(height),
+    };
+    edge as f32 / 2.0
+}
+
+/// Returns the number of pixels per unit of the flame
+pub fn pixels_per_unit(options: &RenderOptions) -> f32 {
+    fit_pixels_per_unit(options.width, options.height, options.fit)
+        * options.scale
+        * libm::exp2f(options.zoom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Camera of a 200x100 picture, with the default framing changed by `options`
+    fn camera(options: &[(&str, f32)], fit: Fit, supersample: u32) -> Camera {
+        let mut builder = RenderOptions::builder()
+            .size(200, 100)
+            .fit(fit)
+            .supersample(supersample);
+        for &(name, value) in options {
+            builder = match name {
+                "center" => builder.center(value, value / 2.0),
+                "scale" => builder.scale(value),
+                "zoom" => builder.zoom(value),
+                "rotate" => builder.rotate(value),
+                _ => unreachable!(),
+            };
+        }
+        Camera::new(&builder.build().unwrap())
+    }
+
+    #[test]
+    fn points_land_on_their_pixels() {
+        // Points are offset by half a pixel from the edges of the pixels they land in
+        let cases = [
+            // The shorter edge spans [-1, 1]: 50 pixels per unit, the longer edge shows more
+            (
+                camera(&[], Fit::ShorterEdge, 1),
+                (0.01, 0.01),
+                Some((50, 100)),
+            ),
+            (
+                camera(&[], Fit::ShorterEdge, 1),
+                (-0.99, -0.99),
+                Some((0, 50)),
+            ),
+            (
+                camera(&[], Fit::ShorterEdge, 1),
+                (1.99, 0.99),
+                Some((99, 199)),
+            ),
+            (camera(&[], Fit::ShorterEdge, 1), (0.01, 1.01), None),
+            // The longer edge spans [-1, 1]: 100 pixels per unit, y is cropped to [-0.5, 0.5]
+            (
+                camera(&[], Fit::LongerEdge, 1),
+                (0.995, 0.495),
+                Some((99, 199)),
+            ),
+            (camera(&[], Fit::LongerEdge, 1), (0.005, 0.505), None),
+            // Twice the pixels along each edge
+            (
+                camera(&[], Fit::ShorterEdge, 2),
+                (0.015, 0.015),
+                Some((101, 201)),
+            ),
+            // 50 * 2 * 2^1 = 200 pixels per unit around (0.5, 0.25)
+            (
+                camera(
+                    &[("center", 0.5), ("scale", 2.0), ("zoom", 1.0)],
+                    Fit::ShorterEdge,
+                    1,
+                ),
+                (0.5 + 0.1025, 0.25 - 0.0475),
+                Some((40, 120)),
+            ),
+            // Turning the camera by 90 degrees shows the x axis going up
+            (
+                camera(&[("rotate", 90.0)], Fit::ShorterEdge, 1),
+                (0.21, 0.01),
+                Some((39, 100)),
+            ),
+            (
+                camera(&[("rotate", 90.0)], Fit::ShorterEdge, 1),
+                (-0.01, 0.41),
+                Some((50, 120)),
+            ),
+        ];
+        for (camera, (x, y), pixel) in cases {
+            assert_eq!(camera.pixel(&Point(x, y)), pixel, "({}, {})", x, y);
+            let index = pixel.map(|(row, column)| (row * camera.width + column) as usize);
+            assert_eq!(camera.index(&Point(x, y)), index, "({}, {})", x, y);
+        }
+    }
+}
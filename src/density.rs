@@ -0,0 +1,159 @@
+//! Density estimation, following flam3
+//!
+//! Each sample only hits one cell, so sparse parts of a flame look like noise. Density estimation
+//! spreads the hits of each cell over its neighbours with a kernel whose radius shrinks as the
+//! number of hits grows: `estimator_radius / hits^estimator_curve`, never less than
+//! `estimator_minimum`. Dense cells stay sharp while isolated hits are blurred into a smooth
+//! glow.
+//!
+//! Radii are given in pixels of the picture, and the hits of a cell are counted over the whole
+//! pixel it belongs to, so that supersampling does not change the amount of blur.
+
+use std::collections::HashMap;
+
+use super::histogram::{Histogram, COLOR_ONE};
+use super::options::RenderOptions;
+
+/// Number of kernels per cell of radius. Radii are rounded so that kernels can be reused.
+const KERNEL_STEPS: f64 = 10.0;
+
+/// Red, green, blue and alpha of a cell after log density scaling
+pub type Accumulation = (f64, f64, f64, f64);
+
+/// Offsets and weights of a normalized gaussian kernel
+type Kernel = Vec<(i64, i64, f64)>;
+
+/// Scales the hits of every cell by `log_scale` and spreads them into `accumulation`
+pub fn estimate<F>(
+    histogram: &Histogram,
+    options: &RenderOptions,
+    log_scale: F,
+    accumulation: &mut [Accumulation],
+) where
+    F: Fn(f64) -> f64,
+{
+    let supersample = options.supersample as i64;
+    let (width, height) = options.supersampled_size();
+    let (width, height) = (width as i64, height as i64);
+    let max_radius = (options.estimator_radius * options.supersample as f32) as f64;
+    let min_radius =
+        ((options.estimator_minimum * options.supersample as f32) as f64).min(max_radius);
+
+    // Hits of every pixel of the picture
+    let pixel_width = options.width as i64;
+    let mut pixel_hits = vec![0u64; (options.width * options.height) as usize];
+    for (idx, &hits) in histogram.counter.iter().enumerate() {
+        let (row, column) = (idx as i64 / width, idx as i64 % width);
+        pixel_hits[((row / supersample) * pixel_width + column / supersample) as usize] += hits;
+    }
+    let curve = options.estimator_curve as f64;
+    let mut kernels: HashMap<u32, Kernel> = HashMap::new();
+
+    for (idx, (&hits, &color)) in histogram.counter.iter().zip(&histogram.color).enumerate() {
+        if hits == 0 {
+            continue;
+        }
+        let hits = hits as f64;
+        let scale = log_scale(hits);
+        let value = (
+            color.0 as f64 / COLOR_ONE as f64 * scale,
+            color.1 as f64 / COLOR_ONE as f64 * scale,
+            color.2 as f64 / COLOR_ONE as f64 * scale,
+            hits * scale,
+        );
+
+        let (row, column) = (idx as i64 / width, idx as i64 % width);
+        let density =
+            pixel_hits[((row / supersample) * pixel_width + column / supersample) as usize];
+        let radius = (max_radius / libm::pow(density as f64, curve)).clamp(min_radius, max_radius);
+        let step = (radius * KERNEL_STEPS).round() as u32;
+        let kernel = kernels
+            .entry(step)
+            .or_insert_with(|| kernel(step as f64 / KERNEL_STEPS));
+        for &(d_row, d_column, weight) in kernel.iter() {
+            let (row, column) = (row + d_row, column + d_column);
+            if row < 0 || row >= height || column < 0 || column >= width {
+                continue;
+            }
+            let cell = &mut accumulation[(row * width + column) as usize];
+            cell.0 += value.0 * weight;
+            cell.1 += value.1 * weight;
+            cell.2 += value.2 * weight;
+            cell.3 += value.3 * weight;
+        }
+    }
+}
+
+/// Returns the gaussian kernel of `radius` pixels, `exp(-2 (d / radius)^2)` for the cells at a
+/// distance `d` of at most `radius`, normalized to a sum of one
+fn kernel(radius: f64) -> Kernel {
+    let extent = radius.floor() as i64;
+    let mut kernel = Vec::new();
+    for d_row in -extent..=extent {
+        for d_column in -extent..=extent {
+            let distance = ((d_row * d_row + d_column * d_column) as f64).sqrt();
+            if distance <= radius {
+                let weight = if radius > 0.0 {
+                    libm::exp(-2.0 * (distance / radius) * (distance / radius))
+                } else {
+                    1.0
+                };
+                kernel.push((d_row, d_column, weight));
+            }
+        }
+    }
+    let total: f64 = kernel.iter().map(|&(_, _, weight)| weight).sum();
+    for entry in kernel.iter_mut() {
+        entry.2 /= total;
+    }
+    kernel
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 40;
+
+    #[test]
+    fn sparse_hits_are_spread_and_dense_ones_stay() {
+        let options = RenderOptions::builder().size(40, 21).build().unwrap();
+        let mut histogram = Histogram::new(WIDTH * 21);
+        let sparse = 10 * WIDTH + 10;
+        let dense = 10 * WIDTH + 34;
+        histogram.add(sparse, (1.0, 0.5, 0.0));
+        for _ in 0..10_000 {
+            histogram.add(dense, (0.0, 0.0, 1.0));
+        }
+        let mut accumulation = vec![(0.0, 0.0, 0.0, 0.0); WIDTH * 21];
+        estimate(&histogram, &options, |_| 1.0, &mut accumulation);
+
+        // The radius of a dense cell, 9 / 10000^0.4, is less than a cell: it keeps its value
+        assert_eq!(accumulation[dense], (0.0, 0.0, 10_000.0, 10_000.0));
+        assert_eq!(accumulation[dense - 1], (0.0, 0.0, 0.0, 0.0));
+        assert_eq!(accumulation[dense + 1], (0.0, 0.0, 0.0, 0.0));
+
+        // A single hit is spread over the cells at most 9 cells away, keeping its total
+        let distance = |idx: usize| {
+            let (d_row, d_column) = ((idx / WIDTH) as f64 - 10.0, (idx % WIDTH) as f64 - 10.0);
+            (d_row * d_row + d_column * d_column).sqrt()
+        };
+        let spread: Vec<usize> = (0..accumulation.len())
+            .filter(|&idx| idx != dense && accumulation[idx].3 > 0.0)
+            .collect();
+        assert!(spread.iter().all(|&idx| distance(idx) <= 9.0));
+        assert!(spread.contains(&(sparse + 9)));
+        assert!(spread.contains(&(sparse - 9 * WIDTH)));
+        assert_eq!(spread.len(), kernel(9.0).len());
+        let total = spread.iter().fold((0.0, 0.0, 0.0), |total, &idx| {
+            let cell = accumulation[idx];
+            (total.0 + cell.0, total.1 + cell.1, total.2 + cell.3)
+        });
+        assert!((total.0 - 1.0).abs() < 1e-9);
+        assert!((total.1 - 0.5).abs() < 1e-9);
+        assert!((total.2 - 1.0).abs() < 1e-9);
+        // The weights fall off away from the hit
+        assert!(accumulation[sparse].3 > accumulation[sparse + 4].3);
+        assert!(accumulation[sparse].3 < 0.1);
+    }
+}
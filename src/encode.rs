@@ -0,0 +1,99 @@
+//! Encoding of a picture into image files
+//!
+//! PPM and PAM are written without any dependency, PNG uses the `png` crate. Every format can
+//! store 8 or 16 bits per channel, and PAM and PNG can add an alpha channel taken from the B/W
+//! representation of the picture.
+
+use super::Picture;
+
+/// Number of bits per channel
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+}
+
+/// Encodes the picture as a binary PPM (P6)
+pub fn to_ppm(picture: &Picture, depth: BitDepth) -> Vec<u8> {
+    let mut data = format!(
+        "P6\n{} {}\n{}\n",
+        picture.width,
+        picture.height,
+        max_value(depth)
+    )
+    .into_bytes();
+    push_samples(&mut data, picture, depth, false);
+    data
+}
+
+/// Encodes the picture as a PAM (P7), with an alpha channel if `alpha` is set
+pub fn to_pam(picture: &Picture, depth: BitDepth, alpha: bool) -> Vec<u8> {
+    let (channels, tuple_type) = if alpha { (4, "RGB_ALPHA") } else { (3, "RGB") };
+    let mut data = format!(
+        "P7\nWIDTH {}\nHEIGHT {}\nDEPTH {}\nMAXVAL {}\nTUPLTYPE {}\nENDHDR\n",
+        picture.width,
+        picture.height,
+        channels,
+        max_value(depth),
+        tuple_type
+    )
+    .into_bytes();
+    push_samples(&mut data, picture, depth, alpha);
+    data
+}
+
+/// Encodes the picture as a PNG, with an alpha channel if `alpha` is set
+pub fn to_png(picture: &Picture, depth: BitDepth, alpha: bool) -> Vec<u8> {
+    let mut samples = Vec::new();
+    push_samples(&mut samples, picture, depth, alpha);
+
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, picture.width, picture.height);
+    encoder.set_color(if alpha {
+        png::ColorType::Rgba
+    } else {
+        png::ColorType::Rgb
+    });
+    encoder.set_depth(match depth {
+        BitDepth::Eight => png::BitDepth::Eight,
+        BitDepth::Sixteen => png::BitDepth::Sixteen,
+    });
+    // Writing into memory can only fail on an invalid header, and pictures are never empty
+    let mut writer = encoder
+        .write_header()
+        .expect("pictures have a valid PNG header");
+    writer
+        .write_image_data(&samples)
+        .expect("samples match the PNG header");
+    writer.finish().expect("PNG data is written to memory");
+    data
+}
+
+fn max_value(depth: BitDepth) -> u32 {
+    match depth {
+        BitDepth::Eight => 255,
+        BitDepth::Sixteen => 65535,
+    }
+}
+
+/// Appends the samples of all the cells, row by row. Both PNM and PNG store 16-bit samples in
+/// big-endian order.
+fn push_samples(data: &mut Vec<u8>, picture: &Picture, depth: BitDepth, alpha: bool) {
+    let mut push = |value: f32| {
+        let value = value.clamp(0.0, 1.0);
+        match depth {
+            BitDepth::Eight => data.push((value * 255.0).round() as u8),
+            BitDepth::Sixteen => {
+                data.extend_from_slice(&((value * 65535.0).round() as u16).to_be_bytes())
+            }
+        }
+    };
+    for (color, cell_alpha) in picture.cell_color.iter().zip(&picture.cell_alpha) {
+        push(color.0);
+        push(color.1);
+        push(color.2);
+        if alpha {
+            push(*cell_alpha);
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! Spatial reconstruction filters, following flam3
+//!
+//! With supersampling, the chaos game is accumulated on a grid `supersample` times finer than
+//! the picture. Each pixel of the picture is then the weighted average of the cells around its
+//! center, with weights given by the shape of the filter scaled so that one unit of the shape is
+//! `filter_radius` pixels of the picture. The kernel is `2 * support * filter_radius` pixels wide.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use super::density::Accumulation;
+use super::options::RenderOptions;
+
+/// Shape of the reconstruction filter
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum FilterShape {
+    Gaussian,
+    Hermite,
+    Box,
+    Mitchell,
+    Lanczos,
+}
+
+impl FilterShape {
+    /// Returns the flam3 name of the shape
+    pub fn name(self) -> &'static str {
+        match self {
+            FilterShape::Gaussian => "gaussian",
+            FilterShape::Hermite => "hermite",
+            FilterShape::Box => "box",
+            FilterShape::Mitchell => "mitchell",
+            FilterShape::Lanczos => "lanczos",
+        }
+    }
+
+    /// Returns the shape of a flam3 name
+    pub fn from_name(name: &str) -> Option<FilterShape> {
+        match name {
+            "gaussian" => Some(FilterShape::Gaussian),
+            "hermite" => Some(FilterShape::Hermite),
+            "box" => Some(FilterShape::Box),
+            "mitchell" => Some(FilterShape::Mitchell),
+            "lanczos" => Some(FilterShape::Lanczos),
+            _ => None,
+        }
+    }
+
+    /// Half width of the shape, outside of which it is zero
+    fn support(self) -> f64 {
+        match self {
+            FilterShape::Gaussian => 1.5,
+            FilterShape::Hermite => 1.0,
+            FilterShape::Box => 0.5,
+            FilterShape::Mitchell => 2.0,
+            FilterShape::Lanczos => 3.0,
+        }
+    }
+
+    /// Value of the shape at `t`. As in flam3, all shapes but the gaussian are zero outside of
+    /// [-support, support].
+    fn value(self, t: f64) -> f64 {
+        let t = t.abs();
+        if t > self.support() && self != FilterShape::Gaussian {
+            return 0.0;
+        }
+        match self {
+            FilterShape::Gaussian => libm::exp(-2.0 * t * t) * (2.0 / PI).sqrt(),
+            FilterShape::Hermite => (2.0 * t - 3.0) * t * t + 1.0,
+            FilterShape::Box => 1.0,
+            FilterShape::Mitchell => {
+                // Mitchell-Netravali with B = C = 1/3
+                let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+                if t < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * t * t * t
+                        + (-18.0 + 12.0 * b + 6.0 * c) * t * t
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                } else {
+                    ((-b - 6.0 * c) * t * t * t
+                        + (6.0 * b + 30.0 * c) * t * t
+                        + (-12.0 * b - 48.0 * c) * t
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                }
+            }
+            FilterShape::Lanczos => sinc(t) * sinc(t / 3.0),
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        libm::sin(PI * x) / (PI * x)
+    }
+}
+
+/// Averages the cells of the supersampled `accumulation` into the pixels of the picture
+pub fn downsample(accumulation: &[Accumulation], options: &RenderOptions) -> Vec<Accumulation> {
+    let supersample = options.supersample as i64;
+    let (width, height) = (options.width as i64, options.height as i64);
+    let (fine_width, fine_height) = (width * supersample, height * supersample);
+    let kernel = kernel(options);
+
+    let mut pixels = vec![(0.0, 0.0, 0.0, 0.0); (width * height) as usize];
+    for row in 0..height {
+        for column in 0..width {
+            let pixel = &mut pixels[(row * width + column) as usize];
+            for &(d_row, d_column, weight) in &kernel {
+                let fine_row = row * supersample + d_row;
+                let fine_column = column * supersample + d_column;
+                let inside =
+                    (0..fine_height).contains(&fine_row) && (0..fine_width).contains(&fine_column);
+                if !inside {
+                    continue;
+                }
+                let cell = accumulation[(fine_row * fine_width + fine_column) as usize];
+                pixel.0 += cell.0 * weight;
+                pixel.1 += cell.1 * weight;
+                pixel.2 += cell.2 * weight;
+                pixel.3 += cell.3 * weight;
+            }
+        }
+    }
+    pixels
+}
+
+/// Returns the offsets of the cells from the first cell of a pixel and their weights,
+/// normalized to a sum of one. The weights are separable in rows and columns.
+fn kernel(options: &RenderOptions) -> Vec<(i64, i64, f64)> {
+    let supersample = options.supersample as i64;
+    let shape = options.filter_shape;
+    let weights = weights(shape, supersample, options.filter_radius as f64);
+    // Same parity as the supersampling, so that the kernel is centered on the cells of the pixel
+    let gutter = (weights.len() as i64 - supersample) / 2;
+    let mut kernel = Vec::new();
+    for (row, &row_weight) in weights.iter().enumerate() {
+        for (column, &column_weight) in weights.iter().enumerate() {
+            kernel.push((
+                row as i64 - gutter,
+                column as i64 - gutter,
+                row_weight * column_weight,
+            ));
+        }
+    }
+    let total: f64 = kernel.iter().map(|&(_, _, weight)| weight).sum();
+    for entry in kernel.iter_mut() {
+        entry.2 /= total;
+    }
+    kernel
+}
+
+/// Weights of one row of the kernel, computed as `flam3_create_spatial_filter` does: the kernel
+/// is `2 * support * supersample * radius` cells wide, rounded up to the parity of
+/// `supersample`, and the shape is evaluated at the distance in cells divided by
+/// `supersample * radius`
+fn weights(shape: FilterShape, supersample: i64, radius: f64) -> Vec<f64> {
+    let scale = supersample as f64 * radius;
+    let exact_width = 2.0 * shape.support() * scale;
+    let mut width = exact_width as i64 + 1;
+    if (width ^ supersample) & 1 == 1 {
+        width += 1;
+    }
+    let weights: Vec<f64> = (0..width)
+        .map(|cell| {
+            let distance = cell as f64 + 0.5 - width as f64 / 2.0;
+            if exact_width > 0.0 {
+                shape.value(distance / scale)
+            } else {
+                shape.value(2.0 * distance / width as f64)
+            }
+        })
+        .collect();
+    // A radius so small that no cell falls inside the shape averages the cells of the pixel
+    if weights.iter().all(|&weight| weight == 0.0) {
+        return vec![1.0; width as usize];
+    }
+    weights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernel_widths_match_flam3() {
+        // Widths returned by flam3_create_spatial_filter
+        let cases = [
+            (FilterShape::Gaussian, 1, 0.5, 3),
+            (FilterShape::Gaussian, 2, 0.5, 4),
+            (FilterShape::Gaussian, 3, 1.0, 11),
+            (FilterShape::Hermite, 4, 0.75, 8),
+            (FilterShape::Box, 1, 0.5, 1),
+            (FilterShape::Box, 3, 1.0, 5),
+            (FilterShape::Mitchell, 2, 1.0, 10),
+            (FilterShape::Lanczos, 1, 1.0, 7),
+            (FilterShape::Gaussian, 2, 0.0, 2),
+        ];
+        for &(shape, supersample, radius, width) in &cases {
+            let weights = weights(shape, supersample, radius);
+            assert_eq!(
+                weights.len(),
+                width,
+                "{} filter, supersample {}, radius {}",
+                shape.name(),
+                supersample,
+                radius
+            );
+        }
+    }
+
+    #[test]
+    fn kernel_is_centered_on_the_pixel() {
+        let options = RenderOptions::builder()
+            .supersample(2)
+            .filter_radius(1.0)
+            .build()
+            .unwrap();
+        let kernel = kernel(&options);
+        // 2 * 1.5 * 2 * 1 = 6, rounded up to 7 then to the even 8
+        assert_eq!(kernel.len(), 8 * 8);
+        let offsets: Vec<i64> = kernel.iter().map(|entry| entry.0).collect();
+        assert_eq!(offsets.iter().min(), Some(&-3));
+        assert_eq!(offsets.iter().max(), Some(&4));
+        let weight = |row, column| {
+            kernel
+                .iter()
+                .find(|entry| entry.0 == row && entry.1 == column)
+                .unwrap()
+                .2
+        };
+        assert_eq!(weight(-3, 0), weight(4, 1));
+        assert_eq!(weight(0, 0), weight(1, 1));
+        let total: f64 = kernel.iter().map(|entry| entry.2).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn weights_follow_the_flam3_gaussian() {
+        // Three cells at distances -1, 0 and 1 with a scale of 0.5
+        let weights = weights(FilterShape::Gaussian, 1, 0.5);
+        let expected = (-8.0f64).exp() * (2.0 / PI).sqrt();
+        assert!((weights[0] - expected).abs() < 1e-15);
+        assert!((weights[1] - (2.0 / PI).sqrt()).abs() < 1e-15);
+        assert_eq!(weights[0], weights[2]);
+    }
+}
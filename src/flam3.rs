@@ -0,0 +1,610 @@
+//! Reading and writing flames in the XML format used by flam3 and Apophysis
+//!
+//! A document holds one or more `<flame>` elements (optionally wrapped in a `<flames>` element).
+//! Each flame is made of `<xform>` elements, an optional `<finalxform>` and a palette given either
+//! as `<color index="i" rgb="r g b"/>` entries or as a hex encoded `<palette>`.
+//!
+//! The color index and color speed of each xform and the 256 entries palette are read and
+//! written as is. Older files give the color speed as a `symmetry`, where a symmetry `s` is a
+//! color speed of `(1 - s) / 2`.
+//!
+//! The framing of a flame (`size`, `center`, `scale`, `zoom` and `rotate`), its number of
+//! iterations (`quality`, in samples per pixel), its `hue` and its tone mapping (`gamma`, `gamma_threshold`,
+//! `brightness`, `contrast`, `vibrancy`, the `estimator_*` density estimation settings,
+//! `supersample`, `filter` and `filter_shape`) are read into and written from `RenderOptions`.
+//! flam3 gives the scale in pixels per unit, which is converted to a magnification relative to
+//! the framing of the picture.
+//!
+//! The `palette` attribute of flam3, the number of a palette of the flam3 collection, is ignored
+//! when the flame gives its colors, as flam3 does. The flam3 collection is not embedded, so a
+//! flame giving its palette only by number is rejected.
+//!
+//! The settings flam3 does not know (`fit`, `fuse`, `builtin_palette`, the number of a built-in
+//! palette of this crate, `reverse_palette`, and the `num_xforms` and `variations` of random
+//! flames) are written as extra attributes of `<flame>`, which flam3
+//! ignores, so that writing a flame and reading it back gives the same `RenderOptions`. The
+//! palette of the genome is written as is, and the palette settings are applied when rendering.
+
+use super::camera::{fit_pixels_per_unit, Fit};
+use super::filter::FilterShape;
+use super::genome::*;
+use super::options::RenderOptions;
+use super::palette;
+use super::variations::{variation_index, Variation, FLAM3_VARIATIONS, VARIATIONS};
+use std::fmt;
+use std::str::FromStr;
+
+/// Errors found while reading a flam3 document
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The document is not well-formed XML
+    Xml(String),
+    /// The document does not contain any `<flame>` element
+    NoFlame,
+    /// An attribute is missing or its value cannot be parsed
+    InvalidAttribute {
+        element: String,
+        attribute: String,
+        value: String,
+    },
+    /// An xform uses a variation that is not implemented by this crate, or an attribute that is
+    /// neither a variation, a parameter of one nor a setting of xforms. `xform` is the index of
+    /// the xform in its flame, or `None` for the final xform.
+    UnsupportedVariation { name: String, xform: Option<usize> },
+    /// An xform gives a setting this crate does not implement (`opacity`, `var_color`, `chaos`
+    /// or `plotmode`) a value other than its default, so the flame would render differently.
+    /// `xform` is as in `UnsupportedVariation`.
+    UnsupportedSetting {
+        name: String,
+        value: String,
+        xform: Option<usize>,
+    },
+    /// The palette is not in a supported format
+    InvalidPalette(String),
+    /// The flame has no colors and only gives the number of a palette of the flam3 collection,
+    /// which is not embedded in this crate
+    UnsupportedPalette(u32),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Xml(msg) => write!(f, "invalid XML: {}", msg),
+            Error::NoFlame => write!(f, "no <flame> element found"),
+            Error::InvalidAttribute {
+                element,
+                attribute,
+                value,
+            } => write!(
+                f,
+                "invalid value {:?} for attribute `{}` of <{}>",
+                value, attribute, element
+            ),
+            Error::UnsupportedVariation {
+                name,
+                xform: Some(idx),
+            } => write!(f, "xform {} uses unsupported variation `{}`", idx, name),
+            Error::UnsupportedVariation { name, xform: None } => {
+                write!(f, "final xform uses unsupported variation `{}`", name)
+            }
+            Error::UnsupportedSetting {
+                name,
+                value,
+                xform: Some(idx),
+            } => write!(
+                f,
+                "xform {} sets unsupported `{}` to {:?}",
+                idx, name, value
+            ),
+            Error::UnsupportedSetting {
+                name,
+                value,
+                xform: None,
+            } => write!(f, "final xform sets unsupported `{}` to {:?}", name, value),
+            Error::InvalidPalette(msg) => write!(f, "invalid palette: {}", msg),
+            Error::UnsupportedPalette(number) => write!(
+                f,
+                "the flame has no colors and palette {} of the flam3 collection is not available",
+                number
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Parses all the flames of a flam3 document
+pub fn parse(xml: &str) -> Result<Vec<Genome>, Error> {
+    Ok(parse_with_options(xml)?
+        .into_iter()
+        .map(|(genome, _)| genome)
+        .collect())
+}
+
+/// Parses all the flames of a flam3 document along with their render settings. The settings a
+/// flame does not give keep their default values.
+pub fn parse_with_options(xml: &str) -> Result<Vec<(Genome, RenderOptions)>, Error> {
+    let doc = roxmltree::Document::parse(xml).map_err(|err| Error::Xml(err.to_string()))?;
+    let flames = doc
+        .descendants()
+        .filter(|node| node.has_tag_name("flame"))
+        .map(|node| Ok((parse_flame(node)?, parse_options(node)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    if flames.is_empty() {
+        Err(Error::NoFlame)
+    } else {
+        Ok(flames)
+    }
+}
+
+/// Writes flames as a flam3 document, framed with the default settings
+pub fn write(genomes: &[Genome]) -> String {
+    let options = RenderOptions::default();
+    let mut xml = String::from("<flames>\n");
+    for genome in genomes {
+        write_flame(&mut xml, genome, &options);
+    }
+    xml.push_str("</flames>\n");
+    xml
+}
+
+/// Writes flames as a flam3 document, each one with its own size, framing and tone mapping
+pub fn write_with_options(flames: &[(Genome, RenderOptions)]) -> String {
+    let mut xml = String::from("<flames>\n");
+    for (genome, options) in flames {
+        write_flame(&mut xml, genome, options);
+    }
+    xml.push_str("</flames>\n");
+    xml
+}
+
+fn parse_flame(node: roxmltree::Node) -> Result<Genome, Error> {
+    let palette = parse_palette(node)?;
+    let mut xforms = Vec::new();
+    let mut final_xform = None;
+    for child in node.children().filter(|child| child.is_element()) {
+        match child.tag_name().name() {
+            "xform" => xforms.push(parse_xform(child, Some(xforms.len()))?),
+            "finalxform" => final_xform = Some(parse_xform(child, None)?),
+            _ => (),
+        }
+    }
+    Ok(Genome {
+        xforms,
+        final_xform,
+        palette,
+    })
+}
+
+fn parse_options(node: roxmltree::Node) -> Result<RenderOptions, Error> {
+    let mut options = RenderOptions::default();
+    if let Some(value) = node.attribute("size") {
+        let size = value
+            .split_whitespace()
+            .map(|item| item.parse::<u32>())
+            .collect::<Result<Vec<u32>, _>>();
+        match size.as_deref() {
+            Ok(&[width, height]) => {
+                options.width = width;
+                options.height = height;
+            }
+            _ => return Err(invalid(node, "size", value)),
+        }
+    }
+    if let Some(value) = node.attribute("center") {
+        match parse_floats(node, "center", value)?.as_slice() {
+            &[x, y] => {
+                options.center_x = x;
+                options.center_y = y;
+            }
+            _ => return Err(invalid(node, "center", value)),
+        }
+    }
+    if let Some(value) = node.attribute("fit") {
+        options.fit = Fit::from_name(value).ok_or_else(|| invalid(node, "fit", value))?;
+    }
+    if let Some(value) = node.attribute("scale") {
+        let pixels_per_unit: f64 = parse_value(node, "scale", value)?;
+        let fit_pixels_per_unit = fit_pixels_per_unit(options.width, options.height, options.fit);
+        options.scale = (pixels_per_unit / fit_pixels_per_unit as f64) as f32;
+    }
+    options.zoom = attr_or(node, "zoom", 0.0)?;
+    options.rotate = attr_or(node, "rotate", 0.0)?;
+    if let Some(value) = node.attribute("quality") {
+        let quality: f64 = parse_value(node, "quality", value)?;
+        let pixels = options.width as f64 * options.height as f64;
+        options.iterations = (quality * pixels).round() as u64;
+    }
+    if let Some(value) = node.attribute("fuse") {
+        options.fuse = parse_value(node, "fuse", value)?;
+    }
+    if let Some(value) = node.attribute("num_xforms") {
+        options.num_xforms = parse_value(node, "num_xforms", value)?;
+    }
+    if let Some(value) = node.attribute("variations") {
+        options.variations = value.split_whitespace().map(str::to_string).collect();
+    }
+    options.gamma = attr_or(node, "gamma", options.gamma)?;
+    options.gamma_threshold = attr_or(node, "gamma_threshold", options.gamma_threshold)?;
+    options.brightness = attr_or(node, "brightness", options.brightness)?;
+    options.contrast = attr_or(node, "contrast", options.contrast)?;
+    options.vibrancy = attr_or(node, "vibrancy", options.vibrancy)?;
+    if let Some(value) = node.attribute("palette") {
+        let number = parse_value(node, "palette", value)?;
+        if !has_colors(node) {
+            return Err(Error::UnsupportedPalette(number));
+        }
+    }
+    if let Some(value) = node.attribute("builtin_palette") {
+        options.palette = Some(parse_value(node, "builtin_palette", value)?);
+    }
+    options.hue = attr_or(node, "hue", options.hue)?;
+    if let Some(value) = node.attribute("reverse_palette") {
+        options.reverse_palette = parse_value::<u8>(node, "reverse_palette", value)? != 0;
+    }
+    options.estimator_radius = attr_or(node, "estimator_radius", options.estimator_radius)?;
+    options.estimator_minimum = attr_or(node, "estimator_minimum", options.estimator_minimum)?;
+    options.estimator_curve = attr_or(node, "estimator_curve", options.estimator_curve)?;
+    if let Some(value) = node.attribute("supersample") {
+        options.supersample = parse_value(node, "supersample", value)?;
+    }
+    options.filter_radius = attr_or(node, "filter", options.filter_radius)?;
+    if let Some(value) = node.attribute("filter_shape") {
+        options.filter_shape =
+            FilterShape::from_name(value).ok_or_else(|| invalid(node, "filter_shape", value))?;
+    }
+    Ok(options)
+}
+
+fn parse_xform(node: roxmltree::Node, index: Option<usize>) -> Result<Xform, Error> {
+    let mut variations = Vec::new();
+    for attribute in node.attributes() {
+        let name = attribute.name();
+        if !FLAM3_VARIATIONS.contains(&name) {
+            if !is_default_setting(node, name, attribute.value())? {
+                return Err(Error::UnsupportedSetting {
+                    name: name.to_string(),
+                    value: attribute.value().to_string(),
+                    xform: index,
+                });
+            }
+            if XFORM_ATTRIBUTES.contains(&name) || is_variation_param(name) {
+                continue;
+            }
+            return Err(Error::UnsupportedVariation {
+                name: name.to_string(),
+                xform: index,
+            });
+        }
+        let weight = parse_f32(node, name, attribute.value())?;
+        match variation_index(name) {
+            Some(idx) if weight != 0.0 => {
+                let mut variation = VARIATIONS[idx].create();
+                for param in variation.params() {
+                    variation.set_param(param.name, attr_or(node, param.name, param.default)?);
+                }
+                variations.push((idx, variation, weight));
+            }
+            None if weight != 0.0 => {
+                return Err(Error::UnsupportedVariation {
+                    name: name.to_string(),
+                    xform: index,
+                })
+            }
+            _ => (),
+        }
+    }
+    // Same order as the registry, whatever the order of the attributes
+    variations.sort_by_key(|(idx, _, _)| *idx);
+    let variations = variations
+        .into_iter()
+        .map(|(_, variation, weight)| (variation, weight))
+        .collect();
+
+    let coeffs_pre = match node.attribute("coefs") {
+        Some(value) => parse_coeffs(node, "coefs", value)?,
+        None => return Err(invalid(node, "coefs", "")),
+    };
+    let coeffs_post = match node.attribute("post") {
+        Some(value) => parse_coeffs(node, "post", value)?,
+        None => IDENTITY,
+    };
+
+    // The final xform does not change the colors unless told so
+    let default_symmetry = if index.is_some() { 0.0 } else { 1.0 };
+    let symmetry = attr_or(node, "symmetry", default_symmetry)?;
+    let color_speed = attr_or(node, "color_speed", (1.0 - symmetry) / 2.0)?;
+
+    Ok(Xform {
+        weight: attr_or(node, "weight", 1.0)?,
+        coeffs_pre,
+        coeffs_post,
+        variations,
+        color: attr_or(node, "color", 0.0)?,
+        color_speed,
+    })
+}
+
+/// Attributes of `<xform>` and `<finalxform>` that are not variations. The animation settings and
+/// the name of an xform do not change a still picture and are ignored, while `opacity`,
+/// `var_color`, `chaos` and `plotmode` are only accepted with their default values.
+const XFORM_ATTRIBUTES: &[&str] = &[
+    "weight",
+    "color",
+    "symmetry",
+    "color_speed",
+    "coefs",
+    "post",
+    "animate",
+    "opacity",
+    "var_color",
+    "chaos",
+    "name",
+    "plotmode",
+    "motion_frequency",
+    "motion_function",
+];
+
+/// Returns whether `value` is the default of the xform setting `name`, for the settings of flam3
+/// that are not implemented: full opacity and direct color, transitions to every xform with a
+/// weight of 1, and points that are plotted. Any other attribute is a default.
+fn is_default_setting(node: roxmltree::Node, name: &str, value: &str) -> Result<bool, Error> {
+    Ok(match name {
+        "opacity" | "var_color" => parse_f32(node, name, value)? == 1.0,
+        "chaos" => parse_floats(node, name, value)?
+            .iter()
+            .all(|&weight| weight == 1.0),
+        "plotmode" => value.trim() == "on",
+        _ => true,
+    })
+}
+
+/// Returns whether `name` is a parameter of a flam3 variation, such as `julian_power`. The
+/// parameters of the variations that are not implemented are recognized by their prefix, so that
+/// files giving them with a zero weight still load.
+fn is_variation_param(name: &str) -> bool {
+    let registered = VARIATIONS.iter().any(|variation| {
+        variation
+            .parameters()
+            .iter()
+            .any(|param| param.name == name)
+    });
+    registered
+        || FLAM3_VARIATIONS.iter().any(|variation| {
+            variation_index(variation).is_none()
+                && name
+                    .strip_prefix(variation)
+                    .is_some_and(|rest| rest.starts_with('_'))
+        })
+}
+
+/// Whether a flame gives its colors, as `<color>` entries or as a hex encoded `<palette>`
+fn has_colors(node: roxmltree::Node) -> bool {
+    node.children()
+        .any(|child| child.has_tag_name("color") || child.has_tag_name("palette"))
+}
+
+/// Reads the palette of a flame, either from its `<color>` entries or from a hex encoded
+/// `<palette>`. Returns a white palette when the flame has none.
+fn parse_palette(node: roxmltree::Node) -> Result<Vec<Color>, Error> {
+    let mut palette = Vec::new();
+    for child in node.children().filter(|child| child.is_element()) {
+        match child.tag_name().name() {
+            "color" => {
+                if palette.is_empty() {
+                    palette = vec![(0.0, 0.0, 0.0); PALETTE_SIZE];
+                }
+                let index = attr_or(child, "index", -1.0)?;
+                if index < 0.0 || index as usize >= PALETTE_SIZE {
+                    return Err(invalid(child, "index", &index.to_string()));
+                }
+                let rgb = child.attribute("rgb").unwrap_or("");
+                let channels = parse_floats(child, "rgb", rgb)?;
+                if channels.len() != 3 {
+                    return Err(invalid(child, "rgb", rgb));
+                }
+                palette[index as usize] = (
+                    channels[0] / 255.0,
+                    channels[1] / 255.0,
+                    channels[2] / 255.0,
+                );
+            }
+            "palette" => {
+                let format = child.attribute("format").unwrap_or("RGB");
+                if !format.eq_ignore_ascii_case("RGB") {
+                    return Err(Error::InvalidPalette(format!(
+                        "unsupported format {:?}",
+                        format
+                    )));
+                }
+                let hex: Vec<u8> = child
+                    .text()
+                    .unwrap_or("")
+                    .bytes()
+                    .filter(|byte| !byte.is_ascii_whitespace())
+                    .collect();
+                if !hex.len().is_multiple_of(6) {
+                    return Err(Error::InvalidPalette(
+                        "hex data is not a whole number of colors".to_string(),
+                    ));
+                }
+                palette = hex
+                    .chunks(6)
+                    .map(|color| {
+                        let channel = |idx: usize| {
+                            std::str::from_utf8(&color[idx..idx + 2])
+                                .ok()
+                                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                                .map(|value| value as f32 / 255.0)
+                                .ok_or_else(|| {
+                                    Error::InvalidPalette("invalid hex digit".to_string())
+                                })
+                        };
+                        Ok((channel(0)?, channel(2)?, channel(4)?))
+                    })
+                    .collect::<Result<Vec<Color>, Error>>()?;
+            }
+            _ => (),
+        }
+    }
+    if palette.is_empty() {
+        palette = vec![(1.0, 1.0, 1.0); PALETTE_SIZE];
+    }
+    Ok(palette)
+}
+
+fn write_flame(xml: &mut String, genome: &Genome, options: &RenderOptions) {
+    // Exact in f64, so that dividing by the same number of pixels per unit gives the scale back
+    let scale = options.scale as f64
+        * fit_pixels_per_unit(options.width, options.height, options.fit) as f64;
+    let quality = options.iterations as f64 / (options.width as f64 * options.height as f64);
+    xml.push_str(&format!(
+        concat!(
+            "  <flame version=\"fractal-flames {}\" size=\"{} {}\" fit=\"{}\" ",
+            "center=\"{} {}\" scale=\"{}\" zoom=\"{}\" rotate=\"{}\" quality=\"{}\" ",
+            "fuse=\"{}\" num_xforms=\"{}\" variations=\"{}\" gamma=\"{}\" ",
+            "gamma_threshold=\"{}\" brightness=\"{}\" contrast=\"{}\" vibrancy=\"{}\" ",
+        ),
+        env!("CARGO_PKG_VERSION"),
+        options.width,
+        options.height,
+        options.fit.name(),
+        options.center_x,
+        options.center_y,
+        scale,
+        options.zoom,
+        options.rotate,
+        quality,
+        options.fuse,
+        options.num_xforms,
+        options.variations.join(" "),
+        options.gamma,
+        options.gamma_threshold,
+        options.brightness,
+        options.contrast,
+        options.vibrancy,
+    ));
+    if let Some(palette) = options.palette {
+        xml.push_str(&format!("builtin_palette=\"{}\" ", palette));
+    }
+    xml.push_str(&format!(
+        concat!(
+            "hue=\"{}\" reverse_palette=\"{}\" estimator_radius=\"{}\" ",
+            "estimator_minimum=\"{}\" estimator_curve=\"{}\" supersample=\"{}\" ",
+            "filter=\"{}\" filter_shape=\"{}\">\n"
+        ),
+        options.hue,
+        options.reverse_palette as u8,
+        options.estimator_radius,
+        options.estimator_minimum,
+        options.estimator_curve,
+        options.supersample,
+        options.filter_radius,
+        options.filter_shape.name(),
+    ));
+    for xform in &genome.xforms {
+        write_xform(xml, "xform", xform);
+    }
+    if let Some(final_xform) = &genome.final_xform {
+        write_xform(xml, "finalxform", final_xform);
+    }
+
+    // flam3 palettes always have 256 colors
+    xml.push_str(&format!(
+        "    <palette count=\"{}\" format=\"RGB\">\n",
+        PALETTE_SIZE
+    ));
+    for row in 0..PALETTE_SIZE / 8 {
+        xml.push_str("      ");
+        for idx in row * 8..(row + 1) * 8 {
+            let (r, g, b) =
+                palette::color_at(&genome.palette, (idx as f32 + 0.5) / PALETTE_SIZE as f32);
+            xml.push_str(&format!("{:02X}{:02X}{:02X}", byte(r), byte(g), byte(b)));
+        }
+        xml.push('\n');
+    }
+    xml.push_str("    </palette>\n");
+    xml.push_str("  </flame>\n");
+}
+
+fn write_xform(xml: &mut String, tag: &str, xform: &Xform) {
+    xml.push_str(&format!(
+        "    <{} weight=\"{}\" color=\"{}\" color_speed=\"{}\"",
+        tag, xform.weight, xform.color, xform.color_speed
+    ));
+    for (variation, weight) in &xform.variations {
+        xml.push_str(&format!(" {}=\"{}\"", variation.name(), weight));
+    }
+    for (variation, _) in &xform.variations {
+        write_params(xml, variation.as_ref());
+    }
+    xml.push_str(&format!(" coefs=\"{}\"", format_coeffs(xform.coeffs_pre)));
+    if xform.coeffs_post != IDENTITY {
+        xml.push_str(&format!(" post=\"{}\"", format_coeffs(xform.coeffs_post)));
+    }
+    xml.push_str("/>\n");
+}
+
+fn write_params(xml: &mut String, variation: &dyn Variation) {
+    for param in variation.params() {
+        if let Some(value) = variation.param(param.name) {
+            xml.push_str(&format!(" {}=\"{}\"", param.name, value));
+        }
+    }
+}
+
+const IDENTITY: Coeffs = (1.0, 0.0, 0.0, 0.0, 1.0, 0.0);
+
+/// flam3 lists the coefficients column by column: "a d b e c f"
+fn parse_coeffs(node: roxmltree::Node, attribute: &str, value: &str) -> Result<Coeffs, Error> {
+    match parse_floats(node, attribute, value)?.as_slice() {
+        &[a, d, b, e, c, f] => Ok((a, b, c, d, e, f)),
+        _ => Err(invalid(node, attribute, value)),
+    }
+}
+
+fn format_coeffs(coeffs: Coeffs) -> String {
+    let (a, b, c, d, e, f) = coeffs;
+    format!("{} {} {} {} {} {}", a, d, b, e, c, f)
+}
+
+fn parse_floats(node: roxmltree::Node, attribute: &str, value: &str) -> Result<Vec<f32>, Error> {
+    value
+        .split_whitespace()
+        .map(|item| parse_f32(node, attribute, item))
+        .collect()
+}
+
+fn parse_f32(node: roxmltree::Node, attribute: &str, value: &str) -> Result<f32, Error> {
+    parse_value(node, attribute, value)
+}
+
+fn parse_value<T: FromStr>(
+    node: roxmltree::Node,
+    attribute: &str,
+    value: &str,
+) -> Result<T, Error> {
+    value
+        .trim()
+        .parse::<T>()
+        .map_err(|_| invalid(node, attribute, value))
+}
+
+fn attr_or(node: roxmltree::Node, attribute: &str, default: f32) -> Result<f32, Error> {
+    match node.attribute(attribute) {
+        Some(value) => parse_f32(node, attribute, value),
+        None => Ok(default),
+    }
+}
+
+fn invalid(node: roxmltree::Node, attribute: &str, value: &str) -> Error {
+    Error::InvalidAttribute {
+        element: node.tag_name().name().to_string(),
+        attribute: attribute.to_string(),
+        value: value.to_string(),
+    }
+}
+
+fn byte(channel: f32) -> u8 {
+    (channel.clamp(0.0, 1.0) * 255.0).round() as u8
+}
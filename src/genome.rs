@@ -0,0 +1,253 @@
+//! This module defines the genome of a flame: every ingredient that describes what is drawn,
+//! independently of how it is rendered
+//!
+//! A genome is made of a set of functions (xforms), each one picked with a given probability, a
+//! final function applied after every iteration and the palette used to color the result.
+//!
+//! Colors follow Draves (2003): each xform has a color index in [0, 1], and every time an xform
+//! is applied the color index of the point moves towards it by the xform's color speed. Points
+//! are drawn with the color of the palette at their color index, so flames are shaded with
+//! smooth gradients.
+
+use super::palette;
+use super::point::*;
+use super::rng::Rng;
+use super::variations::*;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Coefficients (a, b, c, d, e, f) of the affine transformation
+/// (x, y) -> (a * x + b * y + c, d * x + e * y + f)
+pub type Coeffs = (f32, f32, f32, f32, f32, f32);
+
+/// RGB color with channels in [0, 1]
+pub type Color = (f32, f32, f32);
+
+/// Number of colors of the palettes of random flames and of flam3 files
+pub const PALETTE_SIZE: usize = 256;
+
+/// A function of the flame: an affine transformation, followed by a weighted sum of variations,
+/// followed by a second affine transformation
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Xform {
+    /// Relative probability of picking this function
+    pub weight: f32,
+    /// Affine transformation applied before the variations
+    pub coeffs_pre: Coeffs,
+    /// Affine transformation applied after the variations
+    pub coeffs_post: Coeffs,
+    /// Variations of the function, with their parameters, and their weights
+    #[cfg_attr(feature = "serde", serde(with = "variations_by_name"))]
+    pub variations: Vec<WeightedVariation>,
+    /// Color index of the xform in the palette, in [0, 1]
+    pub color: f32,
+    /// How far the color index of a point moves towards `color` when the xform is applied: 0
+    /// keeps the color of the point, 1 replaces it with the color of the xform
+    pub color_speed: f32,
+}
+
+/// Description of a flame
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Genome {
+    pub xforms: Vec<Xform>,
+    /// Function applied after every iteration to the point that is drawn, without changing the
+    /// point the chaos game goes on from
+    pub final_xform: Option<Xform>,
+    /// Colors indexed by the color index of the points, usually `PALETTE_SIZE` of them
+    pub palette: Vec<Color>,
+}
+
+/// Options used when creating a random genome
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenomeOptions {
+    /// Number of xforms, not counting the final xform
+    pub num_xforms: usize,
+    /// Names of the variations the xforms are made of. Unknown names are left out.
+    pub variations: Vec<String>,
+}
+
+impl Default for GenomeOptions {
+    fn default() -> Self {
+        GenomeOptions {
+            num_xforms: super::NUM_XFORMS,
+            variations: DEFAULT_VARIATIONS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl Xform {
+    /// Creates an xform with random coefficients, and the variations at positions `variations`
+    /// of the registry with random parameters and weights
+    pub fn random(rng: &mut dyn Rng, variations: &[usize]) -> Xform {
+        Xform {
+            weight: rng.next_f32(),
+            coeffs_pre: random_coeffs(rng),
+            coeffs_post: random_coeffs(rng),
+            variations: random_variations(rng, variations),
+            color: rng.next_f32(),
+            color_speed: 0.5,
+        }
+    }
+
+    /// Returns the color index of a point of color index `color` once the xform is applied
+    pub fn blend_color(&self, color: f32) -> f32 {
+        color + (self.color - color) * self.color_speed
+    }
+
+    /// Applies the xform on a point
+    pub fn apply(&self, coord: Point, rng: &mut dyn Rng) -> Point {
+        let pre_proc = pre_proc(coord.affine(self.coeffs_pre), self.coeffs_pre);
+        Point::apply_variation(&pre_proc, &self.variations, rng).affine(self.coeffs_post)
+    }
+}
+
+impl Genome {
+    /// Randomly chooses coefficients for the affine pre/post transformations, parmeters for
+    /// parametric variations (such as pdj or blob), variations' weights, colors and
+    /// probability of each function.
+    pub fn random(rng: &mut dyn Rng, options: &GenomeOptions) -> Genome {
+        let mut variations: Vec<usize> = options
+            .variations
+            .iter()
+            .filter_map(|name| variation_index(name))
+            .collect();
+        variations.sort_unstable();
+        variations.dedup();
+        let xforms = (0..options.num_xforms)
+            .map(|_| Xform::random(rng, &variations))
+            .collect();
+        // The final xform does not change the colors
+        let final_xform = Some(Xform {
+            color_speed: 0.0,
+            ..Xform::random(rng, &variations)
+        });
+        // Gradient going through one random color per xform
+        let keys: Vec<Color> = (0..options.num_xforms.max(2))
+            .map(|_| (1.0, rng.next_f32(), rng.next_f32()))
+            .collect();
+        let palette = palette::gradient(&keys);
+        Genome {
+            xforms,
+            final_xform,
+            palette,
+        }
+    }
+
+    /// Returns the color of the palette at color index `color`, in [0, 1]
+    pub fn palette_color(&self, color: f32) -> Color {
+        palette::color_at(&self.palette, color)
+    }
+
+    /// Returns the cumulative probability threshold of each xform. A random number in [0, 1)
+    /// selects the first xform whose threshold is greater than the number.
+    pub fn thresholds(&self) -> Vec<f32> {
+        let total: f32 = self.xforms.iter().map(|xform| xform.weight).sum();
+        let mut acc = 0.0;
+        self.xforms
+            .iter()
+            .map(|xform| {
+                acc += xform.weight;
+                acc / total
+            })
+            .collect()
+    }
+}
+
+fn random_coeffs(rng: &mut dyn Rng) -> Coeffs {
+    (
+        rng.range(-1.0, 1.0),
+        rng.range(-1.0, 1.0),
+        rng.range(-0.1, 0.1),
+        rng.range(-1.0, 1.0),
+        rng.range(-1.0, 1.0),
+        rng.range(-0.1, 0.1),
+    )
+}
+
+fn random_variations(rng: &mut dyn Rng, variations: &[usize]) -> Vec<WeightedVariation> {
+    let mut variations: Vec<WeightedVariation> = variations
+        .iter()
+        .map(|&idx| {
+            let mut variation = VARIATIONS[idx].create();
+            variation.randomize(rng);
+            (variation, rng.next_f32())
+        })
+        .collect();
+    let weights_sum: f32 = variations.iter().map(|(_, weight)| weight).sum();
+    for (_, weight) in variations.iter_mut() {
+        *weight /= weights_sum;
+    }
+    variations
+}
+
+/// (De)serializes the variations of an xform as a map from variation name to its weight and
+/// the values of its parameters, such as `{"blob": {"weight": 0.5, "blob_low": 0.2, ...}}`
+#[cfg(feature = "serde")]
+mod variations_by_name {
+    use super::super::variations::{variation_index, Variation, WeightedVariation, VARIATIONS};
+    use serde::de::Error;
+    use serde::ser::SerializeMap;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    /// Weight and parameters of a variation
+    struct Entry<'a>(&'a dyn Variation, f32);
+
+    impl Serialize for Entry<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut map = serializer.serialize_map(Some(1 + self.0.params().len()))?;
+            map.serialize_entry("weight", &self.1)?;
+            for param in self.0.params() {
+                map.serialize_entry(param.name, &self.0.param(param.name))?;
+            }
+            map.end()
+        }
+    }
+
+    pub fn serialize<S: Serializer>(
+        variations: &[WeightedVariation],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            variations
+                .iter()
+                .map(|(variation, weight)| (variation.name(), Entry(variation.as_ref(), *weight))),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<WeightedVariation>, D::Error> {
+        let by_name = BTreeMap::<String, BTreeMap<String, f32>>::deserialize(deserializer)?;
+        let mut variations = Vec::new();
+        for (name, values) in by_name {
+            let idx = variation_index(&name)
+                .ok_or_else(|| D::Error::custom(format!("unknown variation `{}`", name)))?;
+            let mut variation = VARIATIONS[idx].create();
+            let mut weight = 0.0;
+            for (key, value) in values {
+                if key == "weight" {
+                    weight = value;
+                } else if !variation.set_param(&key, value) {
+                    return Err(D::Error::custom(format!(
+                        "unknown parameter `{}` of variation `{}`",
+                        key, name
+                    )));
+                }
+            }
+            variations.push((idx, variation, weight));
+        }
+        // Same order as the registry, whatever the order of the document
+        variations.sort_by_key(|(idx, _, _)| *idx);
+        Ok(variations
+            .into_iter()
+            .map(|(_, variation, weight)| (variation, weight))
+            .collect())
+    }
+}
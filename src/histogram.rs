@@ -0,0 +1,129 @@
+//! Accumulation of the points visited by the chaos game
+//!
+//! Colors are summed in fixed point rather than in floating point, so that adding histograms is
+//! exact: the histograms filled by several walkers can be merged in any order, on any number of
+//! threads, and always give the same sums.
+//!
+//! Histograms rendered in Web Workers are sent back to the page as bytes: for every cell, the
+//! number of hits followed by the three color sums as little-endian
+//! `u64`, all little-endian, and at the end the number of bad values.
+
+use super::genome::Color;
+use std::convert::TryInto;
+use std::fmt;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// Fixed point value of a color channel equal to 1.0
+pub const COLOR_ONE: f32 = 65_536.0;
+
+/// Number of bytes of a cell of a histogram encoded with `Histogram::to_bytes`
+const CELL_BYTES: usize = 4 * 8;
+
+/// Partial render that does not have the size of the picture it is merged into
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartError {
+    /// Number of bytes of a partial render of the picture
+    pub expected: usize,
+    /// Number of bytes received
+    pub found: usize,
+}
+
+impl fmt::Display for PartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "partial render has {} bytes, the picture needs {}",
+            self.found, self.expected
+        )
+    }
+}
+
+impl std::error::Error for PartError {}
+
+#[cfg(feature = "wasm")]
+impl From<PartError> for JsValue {
+    fn from(err: PartError) -> Self {
+        js_sys::Error::new(&err.to_string()).into()
+    }
+}
+
+/// Number of hits and sum of the colors of every cell of a picture
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    pub counter: Vec<u64>,
+    pub color: Vec<(u64, u64, u64)>,
+    /// Number of times a walker's point became NaN or infinite
+    pub bad_values: u64,
+}
+
+impl Histogram {
+    /// Creates an empty histogram of `len` cells
+    pub fn new(len: usize) -> Histogram {
+        Histogram {
+            counter: vec![0; len],
+            color: vec![(0, 0, 0); len],
+            bad_values: 0,
+        }
+    }
+
+    /// Records a hit of the cell `idx` with `color`
+    pub fn add(&mut self, idx: usize, color: Color) {
+        let fixed = |channel: f32| (channel * COLOR_ONE).round() as u64;
+        self.counter[idx] += 1;
+        self.color[idx].0 += fixed(color.0);
+        self.color[idx].1 += fixed(color.1);
+        self.color[idx].2 += fixed(color.2);
+    }
+
+    /// Adds the hits of `other`, which must have the same number of cells
+    pub fn merge(&mut self, other: &Histogram) {
+        for (counter, other) in self.counter.iter_mut().zip(&other.counter) {
+            *counter += other;
+        }
+        for (color, other) in self.color.iter_mut().zip(&other.color) {
+            color.0 += other.0;
+            color.1 += other.1;
+            color.2 += other.2;
+        }
+        self.bad_values += other.bad_values;
+    }
+
+    /// Encodes the histogram as bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.counter.len() * CELL_BYTES + 8);
+        for (counter, color) in self.counter.iter().zip(&self.color) {
+            bytes.extend_from_slice(&counter.to_le_bytes());
+            bytes.extend_from_slice(&color.0.to_le_bytes());
+            bytes.extend_from_slice(&color.1.to_le_bytes());
+            bytes.extend_from_slice(&color.2.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.bad_values.to_le_bytes());
+        bytes
+    }
+
+    /// Adds the hits of a histogram encoded with `to_bytes`, which must have the same number of
+    /// cells
+    pub fn merge_bytes(&mut self, bytes: &[u8]) -> Result<(), PartError> {
+        let expected = self.counter.len() * CELL_BYTES + 8;
+        if bytes.len() != expected {
+            return Err(PartError {
+                expected,
+                found: bytes.len(),
+            });
+        }
+        let u64_at = |cell: &[u8], start: usize| {
+            u64::from_le_bytes(cell[start..start + 8].try_into().unwrap())
+        };
+        let (cells, bad_values) = bytes.split_at(expected - 8);
+        let mut part = Histogram::new(self.counter.len());
+        for (idx, cell) in cells.chunks_exact(CELL_BYTES).enumerate() {
+            part.counter[idx] = u64_at(cell, 0);
+            part.color[idx] = (u64_at(cell, 8), u64_at(cell, 16), u64_at(cell, 24));
+        }
+        part.bad_values = u64_at(bad_values, 0);
+        // Same sums as the threads of a native render
+        self.merge(&part);
+        Ok(())
+    }
+}
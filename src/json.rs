@@ -0,0 +1,177 @@
+//! Versioned JSON representation of a flame and of the settings used to render it
+//!
+//! A document looks like
+//! `{"version": 3, "flame": {"xforms": [...], "final_xform": {...}, "palette": [...]},
+//! "settings": {"width": 512, "height": 512, "iterations": 100000, "gamma": 2.2}}`,
+//! where the variations of each xform are keyed by variation name and hold their weight and
+//! the values of their parameters, such as `{"pdj": {"weight": 0.5, "pdj_a": 1.2, ...}}`.
+//!
+//! Older documents are still read. Version 2 documents kept the weights of the variations and
+//! the parameters of blob, pdj, fan2 and curl apart. In version 1 documents the palette held one
+//! color per xform: each xform gets a band of a 256 colors palette and a color speed of 1, which
+//! draws them with the same colors.
+
+use super::genome::{Genome, PALETTE_SIZE};
+use super::options::RenderOptions;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+
+/// Version of the documents written by `to_json`
+pub const VERSION: u32 = 3;
+
+/// Errors found while reading a JSON document
+#[derive(Debug)]
+pub enum Error {
+    /// The document is not valid JSON or does not describe a flame
+    Json(serde_json::Error),
+    /// The document was written by an incompatible version of the format
+    UnsupportedVersion(u64),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Json(err) => write!(f, "invalid flame: {}", err),
+            Error::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+#[derive(Serialize)]
+struct DocumentRef<'a> {
+    version: u32,
+    flame: &'a Genome,
+    settings: &'a RenderOptions,
+}
+
+#[derive(Deserialize)]
+struct Document {
+    flame: Genome,
+    #[serde(default)]
+    settings: RenderOptions,
+}
+
+/// Writes a flame and its render settings as JSON
+pub fn to_json(genome: &Genome, options: &RenderOptions) -> String {
+    serde_json::to_string(&DocumentRef {
+        version: VERSION,
+        flame: genome,
+        settings: options,
+    })
+    .expect("flames only contain numbers, strings and sequences")
+}
+
+/// Reads a flame and its render settings from JSON
+pub fn from_json(json: &str) -> Result<(Genome, RenderOptions), Error> {
+    let mut value: serde_json::Value = serde_json::from_str(json)?;
+    let version = value["version"].as_u64().unwrap_or(0);
+    match version {
+        1 => {
+            upgrade_v1(&mut value["flame"]);
+            upgrade_v2(&mut value["flame"]);
+        }
+        2 => upgrade_v2(&mut value["flame"]),
+        3 => (),
+        _ => return Err(Error::UnsupportedVersion(version)),
+    }
+    let document: Document = serde_json::from_value(value)?;
+    Ok((document.flame, document.settings))
+}
+
+/// Turns the one color per xform of a version 1 flame into a palette with one band per xform
+fn upgrade_v1(flame: &mut Value) {
+    let colors = flame["palette"].as_array().cloned().unwrap_or_default();
+    let num_xforms = flame["xforms"].as_array().map_or(0, Vec::len).max(1);
+    if let Some(xforms) = flame["xforms"].as_array_mut() {
+        for (idx, xform) in xforms.iter_mut().enumerate() {
+            xform["color"] = json!((idx as f32 + 0.5) / num_xforms as f32);
+            xform["color_speed"] = json!(1.0);
+        }
+    }
+    if flame["final_xform"].is_object() {
+        flame["final_xform"]["color"] = json!(0.0);
+        flame["final_xform"]["color_speed"] = json!(0.0);
+    }
+    let white = json!([1.0, 1.0, 1.0]);
+    let palette: Vec<Value> = (0..PALETTE_SIZE)
+        .map(|idx| {
+            colors
+                .get(idx * num_xforms / PALETTE_SIZE)
+                .unwrap_or(&white)
+                .clone()
+        })
+        .collect();
+    flame["palette"] = Value::Array(palette);
+}
+
+/// Name of a parameter in version 2 documents and in the variation
+type Renames = &'static [(&'static str, &'static str)];
+
+/// Parameters of version 2 documents: variation, group of parameters and the names of its
+/// parameters
+const V2_PARAMS: &[(&str, &str, Renames)] = &[
+    (
+        "blob",
+        "blob",
+        &[
+            ("low", "blob_low"),
+            ("high", "blob_high"),
+            ("waves", "blob_waves"),
+        ],
+    ),
+    (
+        "pdj",
+        "pdj",
+        &[
+            ("a", "pdj_a"),
+            ("b", "pdj_b"),
+            ("c", "pdj_c"),
+            ("d", "pdj_d"),
+        ],
+    ),
+    ("fan2", "fan", &[("x", "fan2_x"), ("y", "fan2_y")]),
+    ("curl", "curl", &[("c1", "curl_c1"), ("c2", "curl_c2")]),
+];
+
+/// Gathers the weight and the parameters of each variation of a version 2 flame
+fn upgrade_v2(flame: &mut Value) {
+    let upgrade = |xform: &mut Value| {
+        let params = xform["params"].take();
+        let weights = xform["weights"].take();
+        let mut variations = serde_json::Map::new();
+        for (name, weight) in weights.as_object().into_iter().flatten() {
+            let mut variation = serde_json::Map::new();
+            variation.insert("weight".to_string(), weight.clone());
+            if let Some((_, group, names)) = V2_PARAMS.iter().find(|(known, _, _)| known == name) {
+                for (old, new) in names.iter() {
+                    if let Some(value) = params[group].get(old) {
+                        variation.insert(new.to_string(), value.clone());
+                    }
+                }
+            }
+            variations.insert(name.clone(), Value::Object(variation));
+        }
+        if let Some(xform) = xform.as_object_mut() {
+            xform.remove("params");
+            xform.remove("weights");
+            xform.insert("variations".to_string(), Value::Object(variations));
+        }
+    };
+    if let Some(xforms) = flame["xforms"].as_array_mut() {
+        xforms.iter_mut().for_each(upgrade);
+    }
+    if flame["final_xform"].is_object() {
+        upgrade(&mut flame["final_xform"]);
+    }
+}
@@ -1,13 +1,41 @@
 //! A library for creating abstract flames inspired by the paper by Draves (2003)
 
+mod camera;
+mod density;
+pub mod encode;
+mod filter;
+pub mod flam3;
+mod genome;
+mod histogram;
+#[cfg(feature = "serde")]
+pub mod json;
+mod options;
+pub mod palette;
 mod point;
+mod rng;
+mod tone_map;
 mod utils;
 mod variations;
+mod walker;
 
-use point::*;
-use variations::*;
+use camera::Camera;
+use histogram::Histogram;
+pub use histogram::PartError;
+pub use point::Point;
+use walker::Walker;
 
-use js_sys;
+pub use camera::Fit;
+pub use filter::FilterShape;
+pub use genome::{Coeffs, Color, Genome, GenomeOptions, Xform, PALETTE_SIZE};
+pub use options::{OptionsError, RenderOptions, RenderOptionsBuilder, MAX_CELLS};
+pub use rng::{random_seed, Pcg32, Rng};
+pub use variations::{
+    create_variation, variation_index, Blob, Curl, Fan2, JuliaScope, Julian, Kind, Ngon, ParamInfo,
+    Pdj, Perspective, Pie, RadialBlur, Rectangles, Rings2, Variation, VariationFn, VariationInfo,
+    WeightedFn, WeightedVariation, DEFAULT_VARIATIONS, FLAM3_VARIATIONS, VARIATIONS,
+};
+
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 // use web_sys;
 
@@ -25,62 +53,241 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 const PIC_WIDTH: u32 = 512;
 const PIC_HEIGHT: u32 = 512;
-const ITER: usize = 100_000;
-const NUM_FUNCTIONS: usize = 6;
+const ITER: u64 = 100_000;
+const NUM_XFORMS: usize = 6;
 const GAMMA: f32 = 2.2;
+const GAMMA_THRESHOLD: f32 = 0.01;
+const BRIGHTNESS: f32 = 4.0;
+const CONTRAST: f32 = 1.0;
+const VIBRANCY: f32 = 1.0;
+const ESTIMATOR_RADIUS: f32 = 9.0;
+const ESTIMATOR_MINIMUM: f32 = 0.0;
+const ESTIMATOR_CURVE: f32 = 0.4;
+const SUPERSAMPLE: u32 = 1;
+const FILTER_RADIUS: f32 = 0.5;
+const FUSE: u32 = 20;
+/// Number of independent walkers that share the iterations of a render. It does not depend on
+/// the number of threads, so that a seed always gives the same picture.
+const WALKERS: u64 = 64;
+
+/// Statistics of the flame being drawn
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct RenderStats {
+    /// Number of iterations run since the flame was started
+    pub iterations: u64,
+    /// Number of times the point of a walker became NaN or infinite, and the walker started
+    /// again from a random point
+    pub bad_values: u64,
+}
 
 /// Representation of the picture
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct Picture {
+    options: RenderOptions,
     width: u32,
     height: u32,
-    /// Total number of times a particular cell is selected by the algorithm
-    cell_counter: Vec<u32>,
+    camera: Camera,
+    /// Last flame drawn on the picture
+    genome: Option<Genome>,
+    /// Hits of the flame being drawn, before tone mapping
+    histogram: Histogram,
+    /// Palette of the flame being drawn, once the palette settings are applied
+    palette: Vec<Color>,
+    /// Palette given to the random flames instead of a random one
+    custom_palette: Option<Vec<Color>>,
+    /// Walkers of the flame being drawn, empty until a flame is started
+    walkers: Vec<Walker>,
+    /// Number of iterations run since the flame was started
+    iterations_done: u64,
     /// Representation of the color of the cell in B/W scale.
     cell_alpha: Vec<f32>,
     /// Representation of the color of the cell in RGB scale.
     cell_color: Vec<(f32, f32, f32)>,
+    /// Partial renders merged with `merge_part` and not drawn yet
+    parts: Option<Histogram>,
 }
 
 impl Picture {
-    fn get_index(&self, row: u32, column: u32) -> usize {
-        (row * self.width + column) as usize
-    }
-
-    /// Given the coordinates of a point in [-1, 1]x[-1, 1],
-    /// returns its corresponding index in Picture
-    fn get_index_from_coord(&self, coord: &Point) -> Option<usize> {
-        let Point(x, y) = coord;
-        if x.abs() >= 1.0 || y.abs() >= 1.0 {
-            None
-        } else {
-            let a = ((x + 1.0) / 2.0 * self.width as f32).floor() as u32;
-            let b = ((y + 1.0) / 2.0 * self.height as f32).floor() as u32;
-            Some(self.get_index(
-                std::cmp::min(a, self.width - 1),
-                std::cmp::min(b, self.height - 1),
-            ))
+    /// Returns the settings used to render the picture
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    /// Returns the last flame drawn on the picture
+    pub fn genome(&self) -> Option<&Genome> {
+        self.genome.as_ref()
+    }
+
+    /// Returns the RGB color of every cell, row by row
+    pub fn colors(&self) -> &[(f32, f32, f32)] {
+        &self.cell_color
+    }
+
+    /// Returns the number of hits of every cell of the accumulation grid since the flame was
+    /// started, row by row. The grid is `supersample` times larger than the picture.
+    pub fn hits(&self) -> &[u64] {
+        &self.histogram.counter
+    }
+
+    /// Sets the palette given to the random flames instead of a random one, or `None` to go
+    /// back to random palettes
+    pub fn set_palette(&mut self, palette: Option<Vec<Color>>) {
+        self.custom_palette = palette;
+    }
+
+    /// Creates a random Fractal Flame drawing every random choice from `rng`
+    pub fn paint_with_rng(&mut self, rng: &mut dyn Rng) {
+        let genome = self.random_genome(rng);
+        self.render(&genome, rng);
+    }
+
+    /// Draws the flame described by `genome`
+    pub fn render(&mut self, genome: &Genome, rng: &mut dyn Rng) {
+        self.start(genome, rng);
+        self.iterate(self.options.iterations);
+        self.tone_map();
+    }
+
+    /// Draws the flame described by `genome`, sharing the walkers between `threads` threads.
+    /// The picture is the same as the one drawn by `render` whatever the number of threads.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn render_with_threads(&mut self, genome: &Genome, rng: &mut dyn Rng, threads: usize) {
+        self.start(genome, rng);
+        self.iterate_with_threads(self.options.iterations, threads);
+        self.tone_map();
+    }
+
+    /// Starts drawing the flame described by `genome`: clears the hits of the previous flame, so
+    /// that painting twice does not mix two flames, and places the walkers on the attractor.
+    /// Nothing is drawn until `iterate` and `tone_map` are called.
+    pub fn start(&mut self, genome: &Genome, rng: &mut dyn Rng) {
+        let seed = rng.next_u64();
+        self.histogram = Histogram::new(self.histogram.counter.len());
+        self.walkers = (0..WALKERS)
+            .map(|index| Walker::new(genome, seed, index, self.options.fuse))
+            .collect();
+        self.iterations_done = 0;
+        self.palette = palette::select(&genome.palette, &self.options);
+        self.genome = Some(genome.clone());
+    }
+
+    /// Runs `iterations` more iterations of the flame being drawn, sharing the walkers between
+    /// `threads` threads. The hits are the same as the ones of `iterate` whatever the number of
+    /// threads.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn iterate_with_threads(&mut self, iterations: u64, threads: usize) {
+        let genome = match &self.genome {
+            Some(genome) if !self.walkers.is_empty() => genome,
+            _ => return,
+        };
+        let threads = threads.clamp(1, self.walkers.len());
+        let len = self.histogram.counter.len();
+        let camera = &self.camera;
+        let cell = |coord: &Point| camera.index(coord);
+        let chunk = self.walkers.len().div_ceil(threads);
+        let done = self.iterations_done;
+        let palette = &self.palette;
+        let walkers = &mut self.walkers;
+        let histograms: Vec<Histogram> = std::thread::scope(|scope| {
+            let handles: Vec<_> = walkers
+                .chunks_mut(chunk)
+                .enumerate()
+                .map(|(chunk_idx, walkers)| {
+                    scope.spawn(move || {
+                        let mut histogram = Histogram::new(len);
+                        for (offset, walker) in walkers.iter_mut().enumerate() {
+                            let number = (chunk_idx * chunk + offset) as u64;
+                            let share = walker::share_after(done, iterations, number);
+                            walker.run(genome, palette, share, cell, &mut histogram);
+                        }
+                        histogram
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("walker thread panicked"))
+                .collect()
+        });
+        for histogram in &histograms {
+            self.histogram.merge(histogram);
+        }
+        self.iterations_done += iterations;
+    }
+
+    /// Returns the random flame of `seed` and the generator it was drawn from, as in
+    /// `paint_with_seed`
+    fn random_flame(&self, seed: u64) -> (Genome, Pcg32) {
+        let mut rng = Pcg32::new(seed);
+        let genome = self.random_genome(&mut rng);
+        (genome, rng)
+    }
+
+    /// Returns a random flame, with the custom palette if one is set
+    fn random_genome(&self, rng: &mut dyn Rng) -> Genome {
+        let genome_options = GenomeOptions {
+            num_xforms: self.options.num_xforms,
+            variations: self.options.variations.clone(),
+        };
+        let genome = Genome::random(rng, &genome_options);
+        match &self.custom_palette {
+            Some(palette) => Genome {
+                palette: palette.clone(),
+                ..genome
+            },
+            None => genome,
         }
     }
 }
 
-#[wasm_bindgen]
+fn bit_depth(sixteen_bit: bool) -> encode::BitDepth {
+    if sixteen_bit {
+        encode::BitDepth::Sixteen
+    } else {
+        encode::BitDepth::Eight
+    }
+}
+
+impl Default for Picture {
+    fn default() -> Self {
+        Picture::new()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Picture {
     pub fn new() -> Picture {
+        Picture::with_options(RenderOptions::default()).expect("default options are valid")
+    }
+
+    /// Creates an empty picture rendered with `options`
+    pub fn with_options(options: RenderOptions) -> Result<Picture, OptionsError> {
         utils::set_panic_hook();
-        let width = PIC_WIDTH;
-        let height = PIC_HEIGHT;
-        let cell_counter = (0..width * height).map(|_| 1).collect();
+        options.validate()?;
+        let width = options.width;
+        let height = options.height;
         let cell_alpha = (0..width * height).map(|_| 0.0).collect();
         let cell_color = (0..width * height).map(|_| (1.0, 1.0, 1.0)).collect();
 
-        Picture {
+        let camera = Camera::new(&options);
+        let (fine_width, fine_height) = options.supersampled_size();
+
+        Ok(Picture {
+            options,
             width,
             height,
-            cell_counter,
+            camera,
+            genome: None,
+            palette: Vec::new(),
+            custom_palette: None,
+            histogram: Histogram::new(fine_width as usize * fine_height as usize),
+            walkers: Vec::new(),
+            iterations_done: 0,
             cell_alpha,
             cell_color,
-        }
+            parts: None,
+        })
     }
 
     /// Returns width
@@ -93,10 +300,10 @@ impl Picture {
         self.height
     }
 
-    /// Returns a pointer to the array that contains the number of times individual cells have been
-    /// hit
-    pub fn cell_counter(&self) -> *const u32 {
-        self.cell_counter.as_slice().as_ptr()
+    /// Returns a pointer to the array that contains the number of times individual cells of the
+    /// accumulation grid have been hit since the flame was started
+    pub fn cell_counter(&self) -> *const u64 {
+        self.histogram.counter.as_slice().as_ptr()
     }
 
     /// Returns a pointer to the array that contains the B/W color of individual cells
@@ -109,168 +316,197 @@ impl Picture {
         self.cell_color.as_slice().as_ptr()
     }
 
-    /// Creates a Fractal Flame
+    /// Returns the picture encoded as a binary PPM
+    pub fn to_ppm(&self, sixteen_bit: bool) -> Vec<u8> {
+        encode::to_ppm(self, bit_depth(sixteen_bit))
+    }
+
+    /// Returns the picture encoded as a PAM, with an alpha channel if `alpha` is set
+    pub fn to_pam(&self, sixteen_bit: bool, alpha: bool) -> Vec<u8> {
+        encode::to_pam(self, bit_depth(sixteen_bit), alpha)
+    }
+
+    /// Returns the picture encoded as a PNG, with an alpha channel if `alpha` is set
+    pub fn to_png(&self, sixteen_bit: bool, alpha: bool) -> Vec<u8> {
+        encode::to_png(self, bit_depth(sixteen_bit), alpha)
+    }
+
+    /// Gives the random flames a palette matching the pixels of a picture, given row by row with
+    /// `channels` samples per pixel (3 for RGB, 4 for RGBA such as a canvas' `ImageData`)
+    pub fn set_palette_from_pixels(
+        &mut self,
+        pixels: &[u8],
+        channels: u32,
+    ) -> Result<(), palette::Error> {
+        self.custom_palette = Some(palette::from_pixels(pixels, channels as usize)?);
+        Ok(())
+    }
+
+    /// Gives the random flames a palette matching a PNG picture
+    pub fn set_palette_from_png(&mut self, data: &[u8]) -> Result<(), palette::Error> {
+        self.custom_palette = Some(palette::from_png(data)?);
+        Ok(())
+    }
+
+    /// Goes back to random palettes for the random flames
+    pub fn clear_palette(&mut self) {
+        self.custom_palette = None;
+    }
+
+    /// Creates a Fractal Flame from a random seed
     pub fn paint(&mut self) {
-        // Variations to be used.
-        // Variations are defined in `src/variations.rs`
-        let vars: Vec<fn(&PreProc) -> Point> = vec![
-            v1, v2, v3, v4, v5, v6, v8, v9, v10, v11, v12, v13, v14, v15, v16, v19, v23, v24, v27,
-            v28, v29, v39, v42,
-        ];
-
-        // Randomly choose coefficients for the affine pre/post transformations, parmeters for
-        // parametric variations (such as pdj or popcorn), variations' weights, colors and
-        // probability threshold of each function.
-        let coeffs_pre = create_coeffs(NUM_FUNCTIONS);
-        let coeffs_post = create_coeffs(NUM_FUNCTIONS);
-        let params = Params::new(NUM_FUNCTIONS);
-        let weights = weigths(NUM_FUNCTIONS, vars.len());
-        let colors = create_colors(NUM_FUNCTIONS);
-        let threshold = prob_dist(NUM_FUNCTIONS);
-
-        let coeffs_pre_final = create_coeffs(1);
-        let coeffs_post_final = create_coeffs(1);
-        let params_final = Params::new(1);
-        let weights_final = weigths(1, vars.len());
-
-        // Sets an initial random point in the canvas and skip the first 20 iterations
-        // After 20 iterations any random point has fallen into the actual
-        // shape of the attractor and we can start recording data for the picture
-        let mut coord = Point(
-            js_sys::Math::random() as f32 * 2.0 - 1.0,
-            js_sys::Math::random() as f32 * 2.0 - 1.0,
-        );
-        let mut col = colors[0];
-        for _ in 0..20 {
-            let val = js_sys::Math::random() as f32;
-            for idx_threshold in 0..NUM_FUNCTIONS {
-                if val < threshold[idx_threshold] {
-                    let pre_proc = pre_proc(
-                        coord.affine(coeffs_pre[idx_threshold]),
-                        coeffs_pre[idx_threshold],
-                        &params[idx_threshold],
-                    );
-                    coord = Point::apply_variation(&pre_proc, &weights[idx_threshold], &vars)
-                        .affine(coeffs_post[idx_threshold]);
-                    break;
-                }
-            }
-        }
-        // Closelly following the algorithm in Draves (2003)
-        //
-        // 1. Pics a random function. Each function has a probability threshold
-        // 2. Applies an affine transformation
-        // 3. Applies a function (a weighted set of variations)
-        // 4. Applies an affine transformation
-        // 5. Applies a final common function
-        // 6. The resulting point is stored as a counter and a color
-        for _ in 0..ITER {
-            let val = js_sys::Math::random() as f32;
-            for idx_threshold in 0..NUM_FUNCTIONS {
-                if val < threshold[idx_threshold] {
-                    let pre_proc = pre_proc(
-                        coord.affine(coeffs_pre[idx_threshold]),
-                        coeffs_pre[idx_threshold],
-                        &params[idx_threshold],
-                    );
-                    coord = Point::apply_variation(&pre_proc, &weights[idx_threshold], &vars)
-                        .affine(coeffs_post[idx_threshold]);
-                    col = colors[idx_threshold];
-                    break;
-                }
-            }
-
-            let pre_proc = pre_proc(
-                coord.affine(coeffs_pre_final[0]),
-                coeffs_pre_final[0],
-                &params_final[0],
+        self.paint_with_seed(random_seed());
+    }
+
+    /// Creates a Fractal Flame from `seed`. The same seed always produces the same picture.
+    pub fn paint_with_seed(&mut self, seed: u64) {
+        self.paint_with_rng(&mut Pcg32::new(seed));
+    }
+
+    /// Starts drawing the flame of `seed`, as `paint_with_seed` does. The picture is drawn
+    /// progressively by calling `iterate` and `tone_map`.
+    pub fn start_with_seed(&mut self, seed: u64) {
+        let (genome, mut rng) = self.random_flame(seed);
+        self.start(&genome, &mut rng);
+    }
+
+    /// Runs `iterations` more iterations of the flame being drawn. Does nothing before a flame
+    /// is started.
+    pub fn iterate(&mut self, iterations: u64) {
+        let genome = match &self.genome {
+            Some(genome) if !self.walkers.is_empty() => genome,
+            _ => return,
+        };
+        let camera = &self.camera;
+        for (idx, walker) in self.walkers.iter_mut().enumerate() {
+            let share = walker::share_after(self.iterations_done, iterations, idx as u64);
+            walker.run(
+                genome,
+                &self.palette,
+                share,
+                |coord| camera.index(coord),
+                &mut self.histogram,
             );
-            coord = Point::apply_variation(&pre_proc, &weights_final[0], &vars)
-                .affine(coeffs_post_final[0]);
-
-            let idx_opt = self.get_index_from_coord(&coord);
-            match idx_opt {
-                Some(idx) => {
-                    self.cell_counter[idx] += 1;
-                    self.cell_color[idx].0 = self.cell_color[idx].0 + col.0;
-                    self.cell_color[idx].1 = self.cell_color[idx].1 + col.1;
-                    self.cell_color[idx].2 = self.cell_color[idx].2 + col.2;
-                }
-                None => (),
-            }
         }
+        self.iterations_done += iterations;
+    }
 
-        // Rescale counter and color for all cells
-        let max_counter = self.cell_counter.iter().cloned().fold(0, u32::max);
-        let log_max_counter = (max_counter as f32).ln();
-        for i in 0..(self.width * self.height) as usize {
-            self.cell_alpha[i] = (self.cell_counter[i] as f32).ln() / log_max_counter;
-            self.cell_color[i].0 = self.cell_color[i].0.ln() / log_max_counter;
-            self.cell_color[i].1 = self.cell_color[i].1.ln() / log_max_counter;
-            self.cell_color[i].2 = self.cell_color[i].2.ln() / log_max_counter;
-            // Gamma correction
-            self.cell_color[i].0 = self.cell_color[i].0.powf(1.0 / GAMMA);
-            self.cell_color[i].1 = self.cell_color[i].1.powf(1.0 / GAMMA);
-            self.cell_color[i].2 = self.cell_color[i].2.powf(1.0 / GAMMA);
-        }
+    /// Returns the number of iterations run since the flame was started
+    pub fn iterations_done(&self) -> u64 {
+        self.iterations_done
+    }
 
-        // Auxiliary functions
-
-        fn create_coeffs(num_functions: usize) -> Vec<(f32, f32, f32, f32, f32, f32)> {
-            (0..num_functions)
-                .map(|_| {
-                    (
-                        js_sys::Math::random() as f32 * 2.0 - 1.0,
-                        js_sys::Math::random() as f32 * 2.0 - 1.0,
-                        js_sys::Math::random() as f32 * 0.2 - 0.1,
-                        js_sys::Math::random() as f32 * 2.0 - 1.0,
-                        js_sys::Math::random() as f32 * 2.0 - 1.0,
-                        js_sys::Math::random() as f32 * 0.2 - 0.1,
-                    )
-                })
-                .collect::<Vec<(f32, f32, f32, f32, f32, f32)>>()
+    /// Returns the statistics of the flame being drawn
+    pub fn stats(&self) -> RenderStats {
+        RenderStats {
+            iterations: self.iterations_done,
+            bad_values: self.histogram.bad_values,
         }
+    }
 
-        fn create_colors(num_functions: usize) -> Vec<(f32, f32, f32)> {
-            (0..num_functions)
-                .map(|_| {
-                    (
-                        // js_sys::Math::random() as f32,
-                        1.0,
-                        js_sys::Math::random() as f32,
-                        js_sys::Math::random() as f32,
-                    )
-                })
-                .collect::<Vec<(f32, f32, f32)>>()
-        }
+    /// Computes the colors of the cells from the hits recorded so far. The hits are kept, so
+    /// the colors can be computed again with other settings.
+    pub fn tone_map(&mut self) {
+        tone_map::tone_map(
+            &self.histogram,
+            &self.options,
+            self.iterations_done,
+            &mut self.cell_alpha,
+            &mut self.cell_color,
+        );
+    }
 
-        fn prob_dist(num_functions: usize) -> Vec<f32> {
-            let mut weights: Vec<f32> = vec![0.0; num_functions];
-            weights[0] = js_sys::Math::random() as f32;
-            for i in 1..num_functions {
-                weights[i] = weights[i - 1] + js_sys::Math::random() as f32;
-            }
-            for i in 0..num_functions {
-                weights[i] /= weights[num_functions - 1];
-            }
-            weights
-        }
+    /// Takes the tone mapping settings of `options` (gamma, gamma threshold, brightness,
+    /// contrast, vibrancy, density estimation and filter) and computes the colors again, without
+    /// running more iterations
+    pub fn set_tone_mapping(&mut self, options: &RenderOptions) -> Result<(), OptionsError> {
+        let options = RenderOptions {
+            gamma: options.gamma,
+            gamma_threshold: options.gamma_threshold,
+            brightness: options.brightness,
+            contrast: options.contrast,
+            vibrancy: options.vibrancy,
+            filter_shape: options.filter_shape,
+            filter_radius: options.filter_radius,
+            estimator_radius: options.estimator_radius,
+            estimator_minimum: options.estimator_minimum,
+            estimator_curve: options.estimator_curve,
+            ..self.options.clone()
+        };
+        options.validate()?;
+        self.options = options;
+        self.tone_map();
+        Ok(())
+    }
 
-        fn weigths(num_functions: usize, num_variations: usize) -> Vec<Vec<f32>> {
-            (0..num_functions)
-                .map(|_| {
-                    let mut weights: Vec<f32> = vec![1.0 / num_variations as f32; num_variations];
-                    for i in 0..num_variations {
-                        // weights[i] = 2.0 / num_variations as f32 * js_sys::Math::random() as f32;
-                        weights[i] = js_sys::Math::random() as f32;
-                    }
-                    let weights_sum: f32 = weights.iter().cloned().sum();
-                    for i in 0..num_variations {
-                        weights[i] /= weights_sum;
-                    }
-                    weights
-                })
-                .collect::<Vec<Vec<f32>>>()
+    /// Changes the gamma and computes the colors again, without running more iterations
+    pub fn set_gamma(&mut self, gamma: f32) -> Result<(), OptionsError> {
+        let options = RenderOptions {
+            gamma,
+            ..self.options.clone()
+        };
+        self.set_tone_mapping(&options)
+    }
+
+    /// Runs the share of `worker` out of `workers` of the iterations of the flame of `seed` and
+    /// returns its hits, without drawing them. Meant to be called from a Web Worker, the result
+    /// is given to `merge_part` on the page.
+    pub fn render_part(&self, seed: u64, worker: u32, workers: u32) -> Vec<u8> {
+        let (genome, mut rng) = self.random_flame(seed);
+        let walker_seed = rng.next_u64();
+        let palette = palette::select(&genome.palette, &self.options);
+        let mut histogram = Histogram::new(self.histogram.counter.len());
+        for index in (worker as u64..WALKERS).step_by(workers.max(1) as usize) {
+            let mut walker = Walker::new(&genome, walker_seed, index, self.options.fuse);
+            let share = walker::share(self.options.iterations, index);
+            walker.run(
+                &genome,
+                &palette,
+                share,
+                |coord| self.camera.index(coord),
+                &mut histogram,
+            );
         }
+        histogram.to_bytes()
+    }
+
+    /// Adds the hits returned by `render_part`
+    pub fn merge_part(&mut self, part: &[u8]) -> Result<(), PartError> {
+        let len = self.histogram.counter.len();
+        self.parts
+            .get_or_insert_with(|| Histogram::new(len))
+            .merge_bytes(part)
+    }
+
+    /// Draws the flame of `seed` from the merged parts. Once the parts of all the workers are
+    /// merged, the picture is the same as the one drawn by `paint_with_seed(seed)`.
+    pub fn finish_parts(&mut self, seed: u64) {
+        let (genome, _) = self.random_flame(seed);
+        let len = self.histogram.counter.len();
+        self.histogram = self.parts.take().unwrap_or_else(|| Histogram::new(len));
+        self.walkers.clear();
+        self.iterations_done = self.options.iterations;
+        self.genome = Some(genome);
+        self.tone_map();
+    }
+}
+
+#[cfg(all(feature = "wasm", feature = "serde"))]
+#[wasm_bindgen]
+impl Picture {
+    /// Returns the last flame drawn on the picture and its render settings as JSON
+    pub fn to_json(&self) -> Option<String> {
+        self.genome
+            .as_ref()
+            .map(|genome| json::to_json(genome, &self.options))
+    }
+
+    /// Creates a picture from a flame saved with `to_json` and draws it
+    pub fn from_json(json: &str) -> Result<Picture, JsValue> {
+        let (genome, options) =
+            json::from_json(json).map_err(|err| JsValue::from_str(&err.to_string()))?;
+        let mut picture = Picture::with_options(options)?;
+        picture.render(&genome, &mut Pcg32::new(random_seed()));
+        Ok(picture)
     }
 }
@@ -0,0 +1,597 @@
+//! Command-line renderer
+//!
+//! ```text
+//! fractal-flames render [--seed N] [--flame FILE] [--size WxH] [--iters N] [--gamma G] [--out FILE]
+//!                        [--center X,Y] [--scale S] [--zoom Z] [--rotate DEG]
+//!                        [--palette NAME | --palette-file FILE[#ENTRY]] [--hue H] [--reverse-palette]
+//! fractal-flames random --count N --out-dir DIR [--seed N] [--size WxH] [--iters N] [--gamma G]
+//!                        [--out FILE]
+//! ```
+//!
+//! Iteration counts accept the `k`, `M` and `G` suffixes, e.g. `--iters 50M`. The format of the
+//! picture (PNG, PPM or PAM) follows the extension of the output file.
+
+use fractal_flames::encode::{self, BitDepth};
+use fractal_flames::palette::{self, NamedPalette};
+use fractal_flames::{
+    flam3, random_seed, Color, FilterShape, Fit, Genome, GenomeOptions, Pcg32, Picture,
+    RenderOptions, RenderOptionsBuilder, Rng,
+};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::Instant;
+
+/// Number of times the progress of a render is reported
+const PROGRESS_STEPS: u64 = 100;
+
+const USAGE: &str = "\
+Usage:
+    fractal-flames render [--seed N] [--flame FILE] [--size WxH] [--iters N] [--gamma G] [--out FILE]
+    fractal-flames random --count N --out-dir DIR [--seed N] [--size WxH] [--iters N] [--gamma G]
+                          [--out FILE]
+
+Both commands also accept [--16bit] [--alpha] to choose the samples of the picture.
+
+Options:
+    --seed N       Seed of the random flame, or of the random choices made while rendering
+                   the flame given by --flame (random by default)
+    --flame FILE   Renders the first flame of a flam3 file instead of a random one, with the
+                   settings of the file changed only by the options given on the command line
+    --size WxH     Size of the picture in pixels (default 512x512)
+    --fit EDGE     Edge of the picture that spans the [-1, 1] range of the flame, `shorter`
+                   shows the whole range and `longer` fills the picture (default shorter)
+    --center X,Y   Point of the flame shown at the center of the picture (default 0,0)
+    --scale S      Magnification relative to the framing given by --fit (default 1)
+    --zoom Z       Magnification as a power of two (default 0)
+    --rotate DEG   Rotation of the camera in degrees (default 0)
+    --supersample N
+                   Cells of the accumulation grid along each pixel (default 1)
+    --filter SHAPE Reconstruction filter: gaussian, hermite, box, mitchell or lanczos
+                   (default gaussian)
+    --filter-radius R
+                   Size in pixels of one unit of the filter shape, as the `filter` of
+                   flam3 (default 0.5)
+    --iters N      Number of iterations, accepts k, M and G suffixes (default 100k)
+    --spp N        Number of iterations given as samples per pixel, instead of --iters
+    --xforms N     Number of xforms of random flames (default 6)
+    --variations LIST
+                   Comma separated names of the variations of random flames, such as
+                   spherical,swirl,julia (default: the 23 variations of the first releases,
+                   from sinusoidal to tangent without heart, popcorn, rings, fan and fan2)
+    --fuse N       Iterations skipped before plotting points (default 20)
+    --gamma G      Gamma correction (default 2.2)
+    --gamma-threshold T
+                   Alpha below which the gamma correction is linear (default 0.01)
+    --brightness B Brightness (default 4)
+    --contrast C   Contrast (default 1)
+    --vibrancy V   Blend between keeping hues (1) and correcting each channel (0) (default 1)
+    --estimator-radius R
+                   Largest radius of the density estimation kernel, 0 disables density
+                   estimation (default 9)
+    --estimator-minimum R
+                   Smallest radius of the density estimation kernel (default 0)
+    --estimator-curve C
+                   How fast the kernel shrinks in dense areas (default 0.4)
+    --palette P    Built-in palette used instead of the palette of the flame, by name or
+                   number; these are the palettes of this program, not flam3's
+    --palette-file FILE[#ENTRY]
+                   Palette used instead of the palette of the flame, read from a flam3
+                   palette collection (.xml), an Apophysis gradient (.ugr), a Fractint map
+                   (.map) or a GIMP gradient (.ggr), or matching the colors of a picture
+                   (.png). ENTRY picks a palette of the file by name or index (default: the
+                   first one)
+    --hue H        Rotation of the hues of the palette, in turns (default 0)
+    --reverse-palette
+                   Uses the palette in reverse order
+    --threads N    Number of rendering threads, the picture does not depend on it
+                   (default: number of CPUs)
+    --out FILE     Output picture, .png, .ppm or .pam (default flame.png). `random` names
+                   its pictures after their seeds, in the format of the extension of FILE
+    --16bit        Writes 16 bits per channel instead of 8
+    --alpha        Adds an alpha channel (PNG and PAM only)
+    --count N      Number of flames created by `random` (default 1)
+    --out-dir DIR  Directory where `random` writes its pictures and flames";
+
+/// Parsed command line
+struct Args {
+    command: String,
+    seed: Option<u64>,
+    flame: Option<String>,
+    /// Palette read from a file, replacing the palette of the flames
+    palette: Option<Vec<Color>>,
+    /// Render settings given on the command line, as flags and their values, applied on top of
+    /// the settings of the flame file with --flame
+    settings: Vec<(String, String)>,
+    /// Default render settings changed by `settings`
+    options: RenderOptions,
+    out: String,
+    depth: BitDepth,
+    alpha: bool,
+    count: usize,
+    threads: usize,
+    out_dir: Option<String>,
+}
+
+fn main() {
+    let args = match parse_args(env::args().skip(1).collect()) {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("error: {}\n\n{}", msg, USAGE);
+            process::exit(2);
+        }
+    };
+    let result = match args.command.as_str() {
+        "render" => render(&args),
+        "random" => random(&args),
+        _ => unreachable!(),
+    };
+    if let Err(msg) = result {
+        eprintln!("error: {}", msg);
+        process::exit(1);
+    }
+}
+
+fn parse_args(argv: Vec<String>) -> Result<Args, String> {
+    let mut argv = argv.into_iter();
+    let command = match argv.next() {
+        Some(command) if command == "render" || command == "random" => command,
+        Some(command) if command == "--help" || command == "-h" => {
+            println!("{}", USAGE);
+            process::exit(0);
+        }
+        Some(command) => return Err(format!("unknown command `{}`", command)),
+        None => return Err("missing command".to_string()),
+    };
+
+    let mut args = Args {
+        command,
+        seed: None,
+        flame: None,
+        palette: None,
+        settings: Vec::new(),
+        options: RenderOptions::default(),
+        out: "flame.png".to_string(),
+        depth: BitDepth::Eight,
+        alpha: false,
+        count: 1,
+        threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        out_dir: None,
+    };
+    while let Some(flag) = argv.next() {
+        match flag.as_str() {
+            "--16bit" => {
+                args.depth = BitDepth::Sixteen;
+                continue;
+            }
+            "--alpha" => {
+                args.alpha = true;
+                continue;
+            }
+            "--reverse-palette" => {
+                args.settings.push((flag, String::new()));
+                continue;
+            }
+            _ => (),
+        }
+        let value = argv
+            .next()
+            .ok_or_else(|| format!("missing value for `{}`", flag))?;
+        match flag.as_str() {
+            "--seed" => args.seed = Some(parse_number(&value, "--seed")?),
+            "--flame" => args.flame = Some(value),
+            "--palette-file" => args.palette = Some(load_palette(&value)?),
+            "--threads" => args.threads = parse_number(&value, "--threads")?,
+            "--out" => args.out = value,
+            "--count" => args.count = parse_number(&value, "--count")?,
+            "--out-dir" => args.out_dir = Some(value),
+            _ => args.settings.push((flag, value)),
+        }
+    }
+    args.options = options(&RenderOptions::default(), &args.settings)?;
+    if args.command == "random" && args.out_dir.is_none() {
+        return Err("`random` needs --out-dir".to_string());
+    }
+    if args.palette.is_some() && args.options.palette.is_some() {
+        return Err("--palette and --palette-file cannot be used together".to_string());
+    }
+    Ok(args)
+}
+
+/// Applies the render settings given on the command line to `base`
+fn options(base: &RenderOptions, settings: &[(String, String)]) -> Result<RenderOptions, String> {
+    let mut builder = base.to_builder();
+    for (flag, value) in settings {
+        builder = apply_setting(builder, flag, value)?;
+    }
+    builder.build().map_err(|err| err.to_string())
+}
+
+fn apply_setting(
+    builder: RenderOptionsBuilder,
+    flag: &str,
+    value: &str,
+) -> Result<RenderOptionsBuilder, String> {
+    Ok(match flag {
+        "--size" => {
+            let (width, height) = parse_size(value)?;
+            builder.size(width, height)
+        }
+        "--fit" => {
+            let fit = Fit::from_name(value)
+                .ok_or_else(|| format!("invalid value {:?} for `--fit`", value))?;
+            builder.fit(fit)
+        }
+        "--center" => {
+            let (x, y) = parse_center(value)?;
+            builder.center(x, y)
+        }
+        "--scale" => builder.scale(parse_number(value, flag)?),
+        "--zoom" => builder.zoom(parse_number(value, flag)?),
+        "--rotate" => builder.rotate(parse_number(value, flag)?),
+        "--supersample" => builder.supersample(parse_number(value, flag)?),
+        "--filter" => {
+            let shape = FilterShape::from_name(value)
+                .ok_or_else(|| format!("invalid value {:?} for `--filter`", value))?;
+            builder.filter_shape(shape)
+        }
+        "--filter-radius" => builder.filter_radius(parse_number(value, flag)?),
+        "--iters" => builder.iterations(parse_count(value)?),
+        "--spp" => builder.samples_per_pixel(parse_number(value, flag)?),
+        "--xforms" => builder.num_xforms(parse_number(value, flag)?),
+        "--variations" => {
+            let names: Vec<&str> = value.split(',').map(str::trim).collect();
+            builder.variations(&names)
+        }
+        "--fuse" => builder.fuse(parse_number(value, flag)?),
+        "--gamma" => builder.gamma(parse_number(value, flag)?),
+        "--gamma-threshold" => builder.gamma_threshold(parse_number(value, flag)?),
+        "--brightness" => builder.brightness(parse_number(value, flag)?),
+        "--contrast" => builder.contrast(parse_number(value, flag)?),
+        "--vibrancy" => builder.vibrancy(parse_number(value, flag)?),
+        "--estimator-radius" => builder.estimator_radius(parse_number(value, flag)?),
+        "--estimator-minimum" => builder.estimator_minimum(parse_number(value, flag)?),
+        "--estimator-curve" => builder.estimator_curve(parse_number(value, flag)?),
+        "--palette" => {
+            let number = palette::builtin_index(value)
+                .or_else(|| value.parse().ok())
+                .ok_or_else(|| format!("unknown palette {:?}", value))?;
+            builder.palette(Some(number))
+        }
+        "--hue" => builder.hue(parse_number(value, flag)?),
+        "--reverse-palette" => builder.reverse_palette(true),
+        _ => return Err(format!("unknown option `{}`", flag)),
+    })
+}
+
+/// Reads the palette of `FILE[#ENTRY]`, in the format given by the extension of the file
+fn load_palette(value: &str) -> Result<Vec<Color>, String> {
+    let (path, entry) = match value.rsplit_once('#') {
+        Some((path, entry)) => (path, Some(entry)),
+        None => (value, None),
+    };
+    let read = || fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err));
+    let palettes = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("xml") => palette::parse_flam3_palettes(&read()?),
+        Some("ugr") => palette::parse_ugr(&read()?),
+        Some("map") => palette::parse_map(&read()?).map(|colors| {
+            vec![NamedPalette {
+                name: String::new(),
+                colors,
+            }]
+        }),
+        Some("ggr") => palette::parse_ggr(&read()?).map(|palette| vec![palette]),
+        Some("png") => palette::from_png_file(path).map(|colors| {
+            vec![NamedPalette {
+                name: String::new(),
+                colors,
+            }]
+        }),
+        _ => {
+            return Err(format!(
+                "{}: unsupported palette format, use a .xml, .ugr, .map, .ggr or .png file",
+                path
+            ))
+        }
+    }
+    .map_err(|err| format!("{}: {}", path, err))?;
+    let found = match entry {
+        None => palettes.into_iter().next(),
+        Some(entry) => match palettes.iter().position(|palette| palette.name == entry) {
+            Some(idx) => palettes.into_iter().nth(idx),
+            None => entry
+                .parse::<usize>()
+                .ok()
+                .and_then(|idx| palettes.into_iter().nth(idx)),
+        },
+    };
+    found
+        .map(|palette| palette.colors)
+        .ok_or_else(|| format!("{}: no palette {:?}", path, entry.unwrap_or("")))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, flag: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {:?} for `{}`", value, flag))
+}
+
+/// Parses "WIDTHxHEIGHT"
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let mut parts = value.split('x');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(width), Some(height), None) => Ok((
+            parse_number(width, "--size")?,
+            parse_number(height, "--size")?,
+        )),
+        _ => Err(format!("invalid size {:?}, expected WIDTHxHEIGHT", value)),
+    }
+}
+
+/// Parses "X,Y"
+fn parse_center(value: &str) -> Result<(f32, f32), String> {
+    let mut parts = value.split(',');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(x), Some(y), None) => Ok((
+            parse_number(x.trim(), "--center")?,
+            parse_number(y.trim(), "--center")?,
+        )),
+        _ => Err(format!("invalid center {:?}, expected X,Y", value)),
+    }
+}
+
+/// Parses a count with an optional `k`, `M` or `G` suffix
+fn parse_count(value: &str) -> Result<u64, String> {
+    let (digits, multiplier) = match value.chars().last() {
+        Some('k') | Some('K') => (&value[..value.len() - 1], 1_000),
+        Some('m') | Some('M') => (&value[..value.len() - 1], 1_000_000),
+        Some('g') | Some('G') => (&value[..value.len() - 1], 1_000_000_000),
+        _ => (value, 1),
+    };
+    let count: u64 = parse_number(digits, "--iters")?;
+    count
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("too many iterations: {}", value))
+}
+
+fn render(args: &Args) -> Result<(), String> {
+    // A flame file only takes the random choices of its walkers from the seed
+    let seed = args.seed.unwrap_or_else(random_seed);
+    eprintln!("seed: {}", seed);
+    let mut rng = Pcg32::new(seed);
+    let picture = match &args.flame {
+        Some(path) => {
+            let (genome, options) = load_flame(path, args)?;
+            draw(&genome, &options, &mut rng, args.threads)?
+        }
+        None => {
+            // Same sequence of random choices as `Picture::paint_with_seed`
+            let genome = with_palette(Genome::random(&mut rng, &genome_options(args)), args);
+            draw(&genome, &args.options, &mut rng, args.threads)?
+        }
+    };
+    save(&picture, Path::new(&args.out), args)
+}
+
+/// Reads the first flame of a flam3 file, with the settings of the file changed by the ones given
+/// on the command line
+fn load_flame(path: &str, args: &Args) -> Result<(Genome, RenderOptions), String> {
+    let xml = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    let mut flames = flam3::parse_with_options(&xml).map_err(|err| format!("{}: {}", path, err))?;
+    if flames.len() > 1 {
+        eprintln!(
+            "{} contains {} flames, rendering the first one",
+            path,
+            flames.len()
+        );
+    }
+    let (genome, mut base) = flames.swap_remove(0);
+    // A palette file replaces the built-in palette of the flame too
+    if args.palette.is_some() {
+        base.palette = None;
+    }
+    let options = options(&base, &args.settings).map_err(|err| format!("{}: {}", path, err))?;
+    Ok((with_palette(genome, args), options))
+}
+
+fn random(args: &Args) -> Result<(), String> {
+    let out_dir = Path::new(args.out_dir.as_ref().unwrap());
+    fs::create_dir_all(out_dir).map_err(|err| format!("{}: {}", out_dir.display(), err))?;
+    // Same format as the picture of `render`
+    let extension = Path::new(&args.out).extension().unwrap_or_default();
+    let mut seeds = Pcg32::new(args.seed.unwrap_or_else(random_seed));
+    for idx in 0..args.count {
+        let seed = seeds.next_u64();
+        eprintln!("[{}/{}] seed: {}", idx + 1, args.count, seed);
+        let mut rng = Pcg32::new(seed);
+        let genome = with_palette(Genome::random(&mut rng, &genome_options(args)), args);
+        let picture = draw(&genome, &args.options, &mut rng, args.threads)?;
+        let path = out_dir.join(seed.to_string()).with_extension(extension);
+        save(&picture, &path, args)?;
+
+        let flame_path = out_dir.join(format!("{}.flam3", seed));
+        fs::write(
+            &flame_path,
+            flam3::write_with_options(&[(genome, args.options.clone())]),
+        )
+        .map_err(|err| format!("{}: {}", flame_path.display(), err))?;
+    }
+    Ok(())
+}
+
+fn genome_options(args: &Args) -> GenomeOptions {
+    GenomeOptions {
+        num_xforms: args.options.num_xforms,
+        variations: args.options.variations.clone(),
+    }
+}
+
+/// Replaces the palette of `genome` with the one given by --palette-file, if any
+fn with_palette(genome: Genome, args: &Args) -> Genome {
+    match &args.palette {
+        Some(palette) => Genome {
+            palette: palette.clone(),
+            ..genome
+        },
+        None => genome,
+    }
+}
+
+/// Renders a flame, reporting the progress and the time taken on stderr
+fn draw(
+    genome: &Genome,
+    options: &RenderOptions,
+    rng: &mut dyn Rng,
+    threads: usize,
+) -> Result<Picture, String> {
+    eprintln!(
+        "rendering {}x{} picture with {} iterations on {} threads...",
+        options.width, options.height, options.iterations, threads
+    );
+    let start = Instant::now();
+    let mut picture = Picture::with_options(options.clone()).map_err(|err| err.to_string())?;
+    picture.start(genome, rng);
+    // The picture does not depend on how the iterations are split
+    let step = options.iterations.div_ceil(PROGRESS_STEPS);
+    while picture.iterations_done() < options.iterations {
+        let iterations = step.min(options.iterations - picture.iterations_done());
+        picture.iterate_with_threads(iterations, threads);
+        eprint!(
+            "\r{:3}%",
+            picture.iterations_done() * 100 / options.iterations
+        );
+    }
+    picture.tone_map();
+    eprintln!("\rrendered in {:.2?}", start.elapsed());
+    let bad_values = picture.stats().bad_values;
+    if bad_values > 0 {
+        eprintln!("{} bad values, restarted from random points", bad_values);
+    }
+    Ok(picture)
+}
+
+/// Saves the picture in the format given by the extension of `path`
+fn save(picture: &Picture, path: &Path, args: &Args) -> Result<(), String> {
+    let data = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("png") => encode::to_png(picture, args.depth, args.alpha),
+        Some("pam") => encode::to_pam(picture, args.depth, args.alpha),
+        Some("ppm") if !args.alpha => encode::to_ppm(picture, args.depth),
+        Some("ppm") => return Err("PPM pictures cannot have an alpha channel".to_string()),
+        _ => {
+            return Err(format!(
+                "{}: unsupported output format, use a .png, .ppm or .pam file",
+                path.display()
+            ))
+        }
+    };
+    fs::write(path, data).map_err(|err| format!("{}: {}", path.display(), err))?;
+    eprintln!("saved {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLAME: &str = r#"<flame size="200 100" quality="5" center="0.1 0.2" scale="80"
+        rotate="30" gamma="3" gamma_threshold="0.05" brightness="2" contrast="1.5"
+        vibrancy="0.5" estimator_radius="3" estimator_minimum="1" estimator_curve="0.7"
+        supersample="2" filter="0.8" filter_shape="mitchell" hue="0.25">
+        <xform weight="1" color="0" linear="1" coefs="0.5 0 0 0.5 -0.5 0"/>
+        <xform weight="1" color="1" spherical="1" coefs="0.5 0 0 0.5 0.5 0"/>
+        <xform weight="1" color="0.5" swirl="1" coefs="0.5 0 0 0.5 0 0.5"/>
+    </flame>"#;
+
+    fn args(argv: &[&str]) -> Args {
+        parse_args(argv.iter().map(|arg| arg.to_string()).collect()).unwrap()
+    }
+
+    /// Creates an empty directory for the files of the test `name`
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = env::temp_dir().join(format!("fractal-flames-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn flames_are_rendered_with_their_settings() {
+        let path = env::temp_dir().join(format!("fractal-flames-{}.flam3", process::id()));
+        fs::write(&path, FLAME).unwrap();
+        let path = path.to_str().unwrap();
+        let args = args(&["render", "--flame", path, "--brightness", "5"]);
+        let loaded = load_flame(path, &args);
+        fs::remove_file(path).unwrap();
+        let (genome, options) = loaded.unwrap();
+        let picture = draw(&genome, &options, &mut Pcg32::new(1), 3).unwrap();
+
+        // The settings of the file, but the brightness given on the command line
+        let (flame, file_options) = flam3::parse_with_options(FLAME).unwrap().remove(0);
+        let expected = file_options.to_builder().brightness(5.0).build().unwrap();
+        assert_eq!(picture.options(), &expected);
+        assert_eq!((picture.width(), picture.height()), (200, 100));
+        assert_eq!(expected.gamma, 3.0);
+        assert_eq!(expected.estimator_radius, 3.0);
+        assert_eq!(expected.filter_shape, FilterShape::Mitchell);
+
+        let mut direct = Picture::with_options(expected).unwrap();
+        direct.render(&flame, &mut Pcg32::new(1));
+        assert_eq!(picture.colors(), direct.colors());
+        let mut defaults = Picture::with_options(RenderOptions {
+            width: 200,
+            height: 100,
+            ..RenderOptions::default()
+        })
+        .unwrap();
+        defaults.render(&flame, &mut Pcg32::new(1));
+        assert_ne!(picture.colors(), defaults.colors());
+    }
+
+    #[test]
+    fn flames_are_rendered_from_the_seed() {
+        let dir = temp_dir("seed");
+        let flame = dir.join("flame.flam3");
+        fs::write(&flame, FLAME).unwrap();
+        let out = dir.join("flame.ppm");
+        let (flame, out) = (flame.to_str().unwrap(), out.to_str().unwrap());
+        let args = args(&["render", "--flame", flame, "--seed", "7", "--out", out]);
+        let rendered = render(&args).map(|()| fs::read(out).unwrap());
+        let loaded = load_flame(flame, &args);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let (genome, options) = loaded.unwrap();
+        let picture = draw(&genome, &options, &mut Pcg32::new(7), 2).unwrap();
+        assert_eq!(rendered.unwrap(), encode::to_ppm(&picture, BitDepth::Eight));
+    }
+
+    #[test]
+    fn random_pictures_have_the_format_of_out() {
+        let dir = temp_dir("random");
+        let argv = [
+            "random",
+            "--out-dir",
+            dir.to_str().unwrap(),
+            "--seed",
+            "3",
+            "--size",
+            "32x32",
+            "--iters",
+            "1k",
+            "--out",
+            "picture.ppm",
+        ];
+        let result = random(&args(&argv));
+        let seed = Pcg32::new(3).next_u64();
+        let picture = fs::read(dir.join(format!("{}.ppm", seed)));
+        let png = dir.join(format!("{}.png", seed)).exists();
+        let flame = dir.join(format!("{}.flam3", seed)).exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        assert!(picture.unwrap().starts_with(b"P6\n32 32\n255\n"));
+        assert!(!png);
+        assert!(flame);
+    }
+}
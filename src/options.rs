@@ -0,0 +1,556 @@
+//! This module defines the settings that describe how a flame is rendered, as opposed to the
+//! genome that describes what is rendered
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use super::camera::Fit;
+use super::filter::FilterShape;
+use super::palette;
+use super::variations::{variation_index, DEFAULT_VARIATIONS};
+use super::{
+    BRIGHTNESS, CONTRAST, ESTIMATOR_CURVE, ESTIMATOR_MINIMUM, ESTIMATOR_RADIUS, FILTER_RADIUS,
+    FUSE, GAMMA, GAMMA_THRESHOLD, ITER, NUM_XFORMS, PIC_HEIGHT, PIC_WIDTH, SUPERSAMPLE, VIBRANCY,
+};
+
+/// Largest number of cells of the accumulation grid, `width * height * supersample²`. Each cell
+/// takes 32 bytes in the histogram and 32 more during tone mapping, so this is about 2 GiB, enough
+/// for a 1920x1080 picture supersampled 4 times.
+pub const MAX_CELLS: u64 = 1 << 25;
+
+/// Settings used to render a flame
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct RenderOptions {
+    /// Width of the picture in pixels
+    pub width: u32,
+    /// Height of the picture in pixels
+    pub height: u32,
+    /// Which edge of the picture spans the [-1, 1] range of the flame
+    pub fit: Fit,
+    /// Horizontal coordinate of the point of the flame shown at the center of the picture
+    pub center_x: f32,
+    /// Vertical coordinate of the point of the flame shown at the center of the picture
+    pub center_y: f32,
+    /// Magnification of the flame, relative to the framing given by `fit`
+    pub scale: f32,
+    /// Magnification of the flame as a power of two, so that each unit doubles its size
+    pub zoom: f32,
+    /// Rotation of the camera in degrees
+    pub rotate: f32,
+    /// Number of cells of the accumulation grid along each pixel of the picture
+    pub supersample: u32,
+    /// Shape of the filter that averages the cells of the accumulation grid into pixels
+    pub filter_shape: FilterShape,
+    /// Size in pixels of the picture of one unit of the filter shape, whose half width is
+    /// `support * filter_radius` pixels as in flam3
+    pub filter_radius: f32,
+    /// Number of iterations of the chaos game
+    pub iterations: u64,
+    /// Number of xforms of the random flames
+    pub num_xforms: usize,
+    /// Names of the variations the random flames are made of
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
+    pub variations: Vec<String>,
+    /// Gamma used to correct the colors of the picture
+    pub gamma: f32,
+    /// Alpha below which the gamma correction is linear, so that faint cells are not amplified
+    pub gamma_threshold: f32,
+    /// Brightness of the picture
+    pub brightness: f32,
+    /// Contrast of the picture
+    pub contrast: f32,
+    /// Blend between correcting the colors with the gamma corrected alpha (1), which keeps the
+    /// hues, and correcting each channel on its own (0)
+    pub vibrancy: f32,
+    /// Largest radius in pixels of the density estimation kernel, used for the cells hit once.
+    /// Zero disables density estimation.
+    pub estimator_radius: f32,
+    /// Smallest radius in pixels of the density estimation kernel
+    pub estimator_minimum: f32,
+    /// How fast the radius of the density estimation kernel shrinks as the number of hits grows
+    pub estimator_curve: f32,
+    /// Number of iterations run before plotting points, so that the point has reached the
+    /// attractor
+    pub fuse: u32,
+    /// Number of the built-in palette used instead of the palette of the flame, if any
+    pub palette: Option<u32>,
+    /// Rotation of the hues of the palette, in turns of the color wheel
+    pub hue: f32,
+    /// Whether the palette is used in reverse order
+    pub reverse_palette: bool,
+}
+
+/// Invalid render settings
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionsError {
+    /// The width or the height is zero
+    EmptyPicture { width: u32, height: u32 },
+    /// The accumulation grid would have more than `MAX_CELLS` cells
+    PictureTooLarge {
+        width: u32,
+        height: u32,
+        supersample: u32,
+    },
+    /// The center, scale, zoom or rotation of the camera is not a valid number
+    InvalidCamera(String),
+    /// Random flames need at least one xform
+    NoXforms,
+    /// A variation is unknown, or none is enabled
+    InvalidVariations(String),
+    /// The gamma is not a positive number
+    InvalidGamma(f32),
+    /// The brightness, contrast, vibrancy or gamma threshold is not a valid number
+    InvalidToneMapping(String),
+    /// The radius, minimum or curve of the density estimation is not a valid number
+    InvalidEstimator(String),
+    /// The supersampling or the radius of the filter is not valid
+    InvalidFilter(String),
+    /// The number of samples per pixel is not a positive number
+    InvalidSamplesPerPixel(f32),
+    /// The built-in palette does not exist or the hue rotation is not a valid number
+    InvalidPalette(String),
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionsError::EmptyPicture { width, height } => {
+                write!(f, "picture size {}x{} is empty", width, height)
+            }
+            OptionsError::PictureTooLarge {
+                width,
+                height,
+                supersample,
+            } => write!(
+                f,
+                "picture size {}x{} supersampled {} times needs more than the maximum of {} cells",
+                width, height, supersample, MAX_CELLS
+            ),
+            OptionsError::InvalidCamera(msg) => write!(f, "invalid camera: {}", msg),
+            OptionsError::NoXforms => write!(f, "flames need at least one xform"),
+            OptionsError::InvalidVariations(msg) => write!(f, "invalid variations: {}", msg),
+            OptionsError::InvalidGamma(gamma) => {
+                write!(f, "gamma must be a positive number, got {}", gamma)
+            }
+            OptionsError::InvalidToneMapping(msg) => write!(f, "invalid tone mapping: {}", msg),
+            OptionsError::InvalidEstimator(msg) => {
+                write!(f, "invalid density estimation: {}", msg)
+            }
+            OptionsError::InvalidFilter(msg) => write!(f, "invalid filter: {}", msg),
+            OptionsError::InvalidSamplesPerPixel(spp) => write!(
+                f,
+                "samples per pixel must be a positive number, got {}",
+                spp
+            ),
+            OptionsError::InvalidPalette(msg) => write!(f, "invalid palette: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for OptionsError {}
+
+#[cfg(feature = "wasm")]
+impl From<OptionsError> for JsValue {
+    fn from(err: OptionsError) -> Self {
+        js_sys::Error::new(&err.to_string()).into()
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            width: PIC_WIDTH,
+            height: PIC_HEIGHT,
+            fit: Fit::ShorterEdge,
+            center_x: 0.0,
+            center_y: 0.0,
+            scale: 1.0,
+            zoom: 0.0,
+            rotate: 0.0,
+            supersample: SUPERSAMPLE,
+            filter_shape: FilterShape::Gaussian,
+            filter_radius: FILTER_RADIUS,
+            iterations: ITER,
+            num_xforms: NUM_XFORMS,
+            variations: DEFAULT_VARIATIONS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            gamma: GAMMA,
+            gamma_threshold: GAMMA_THRESHOLD,
+            brightness: BRIGHTNESS,
+            contrast: CONTRAST,
+            vibrancy: VIBRANCY,
+            estimator_radius: ESTIMATOR_RADIUS,
+            estimator_minimum: ESTIMATOR_MINIMUM,
+            estimator_curve: ESTIMATOR_CURVE,
+            fuse: FUSE,
+            palette: None,
+            hue: 0.0,
+            reverse_palette: false,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Returns the size of the accumulation grid
+    pub(crate) fn supersampled_size(&self) -> (u32, u32) {
+        (
+            self.width.saturating_mul(self.supersample),
+            self.height.saturating_mul(self.supersample),
+        )
+    }
+
+    /// Returns a builder starting from the default settings
+    pub fn builder() -> RenderOptionsBuilder {
+        RenderOptions::default().to_builder()
+    }
+
+    /// Returns a builder starting from these settings, such as the ones of a flam3 file
+    pub fn to_builder(&self) -> RenderOptionsBuilder {
+        RenderOptionsBuilder {
+            options: self.clone(),
+            samples_per_pixel: None,
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl RenderOptions {
+    /// Returns the default settings
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new() -> RenderOptions {
+        RenderOptions::default()
+    }
+
+    /// Checks that the settings describe a picture that can be rendered
+    pub fn validate(&self) -> Result<(), OptionsError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(OptionsError::EmptyPicture {
+                width: self.width,
+                height: self.height,
+            });
+        }
+        if self.supersample == 0 {
+            return Err(OptionsError::InvalidFilter(
+                "supersampling must be at least 1".to_string(),
+            ));
+        }
+        let supersample = self.supersample as u128;
+        let cells = self.width as u128 * self.height as u128 * supersample * supersample;
+        if cells > MAX_CELLS as u128 {
+            return Err(OptionsError::PictureTooLarge {
+                width: self.width,
+                height: self.height,
+                supersample: self.supersample,
+            });
+        }
+        if !(self.filter_radius.is_finite() && self.filter_radius >= 0.0) {
+            return Err(OptionsError::InvalidFilter(format!(
+                "radius must be a non-negative number, got {}",
+                self.filter_radius
+            )));
+        }
+        if !(self.center_x.is_finite() && self.center_y.is_finite()) {
+            return Err(OptionsError::InvalidCamera(format!(
+                "center ({}, {}) is not finite",
+                self.center_x, self.center_y
+            )));
+        }
+        if !(self.scale.is_finite() && self.scale > 0.0) {
+            return Err(OptionsError::InvalidCamera(format!(
+                "scale must be a positive number, got {}",
+                self.scale
+            )));
+        }
+        if !self.zoom.is_finite() || !self.rotate.is_finite() {
+            return Err(OptionsError::InvalidCamera(format!(
+                "zoom {} and rotation {} must be finite",
+                self.zoom, self.rotate
+            )));
+        }
+        if self.num_xforms == 0 {
+            return Err(OptionsError::NoXforms);
+        }
+        if self.variations.is_empty() {
+            return Err(OptionsError::InvalidVariations(
+                "at least one variation must be enabled".to_string(),
+            ));
+        }
+        if let Some(name) = self
+            .variations
+            .iter()
+            .find(|name| variation_index(name).is_none())
+        {
+            return Err(OptionsError::InvalidVariations(format!(
+                "unknown variation `{}`",
+                name
+            )));
+        }
+        if !(self.gamma.is_finite() && self.gamma > 0.0) {
+            return Err(OptionsError::InvalidGamma(self.gamma));
+        }
+        if !(self.brightness.is_finite() && self.brightness > 0.0) {
+            return Err(OptionsError::InvalidToneMapping(format!(
+                "brightness must be a positive number, got {}",
+                self.brightness
+            )));
+        }
+        if !(self.contrast.is_finite() && self.contrast > 0.0) {
+            return Err(OptionsError::InvalidToneMapping(format!(
+                "contrast must be a positive number, got {}",
+                self.contrast
+            )));
+        }
+        if !(0.0..=1.0).contains(&self.vibrancy) {
+            return Err(OptionsError::InvalidToneMapping(format!(
+                "vibrancy must be between 0 and 1, got {}",
+                self.vibrancy
+            )));
+        }
+        if !(self.gamma_threshold.is_finite() && self.gamma_threshold >= 0.0) {
+            return Err(OptionsError::InvalidToneMapping(format!(
+                "gamma threshold must be a non-negative number, got {}",
+                self.gamma_threshold
+            )));
+        }
+        let non_negative = |value: f32| value.is_finite() && value >= 0.0;
+        if !non_negative(self.estimator_radius) || !non_negative(self.estimator_minimum) {
+            return Err(OptionsError::InvalidEstimator(format!(
+                "radius {} and minimum {} must be non-negative numbers",
+                self.estimator_radius, self.estimator_minimum
+            )));
+        }
+        if !(self.estimator_curve.is_finite() && self.estimator_curve > 0.0) {
+            return Err(OptionsError::InvalidEstimator(format!(
+                "curve must be a positive number, got {}",
+                self.estimator_curve
+            )));
+        }
+        if let Some(number) = self.palette {
+            if palette::builtin_name(number).is_none() {
+                return Err(OptionsError::InvalidPalette(format!(
+                    "there is no built-in palette numbered {}",
+                    number
+                )));
+            }
+        }
+        if !self.hue.is_finite() {
+            return Err(OptionsError::InvalidPalette(format!(
+                "hue must be finite, got {}",
+                self.hue
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+impl RenderOptions {
+    /// Returns the names of the variations the random flames are made of
+    #[wasm_bindgen(getter)]
+    pub fn variations(&self) -> Vec<String> {
+        self.variations.clone()
+    }
+
+    /// Sets the names of the variations the random flames are made of
+    #[wasm_bindgen(setter)]
+    pub fn set_variations(&mut self, names: Vec<String>) {
+        self.variations = names;
+    }
+}
+
+/// Builder of `RenderOptions`
+///
+/// ```
+/// # use fractal_flames::RenderOptions;
+/// let options = RenderOptions::builder()
+///     .size(1920, 1080)
+///     .samples_per_pixel(50.0)
+///     .build()
+///     .unwrap();
+/// assert_eq!(options.iterations, 1920 * 1080 * 50);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RenderOptionsBuilder {
+    options: RenderOptions,
+    samples_per_pixel: Option<f32>,
+}
+
+impl RenderOptionsBuilder {
+    /// Sets the size of the picture in pixels
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.options.width = width;
+        self.options.height = height;
+        self
+    }
+
+    /// Sets which edge of the picture spans the [-1, 1] range of the flame
+    pub fn fit(mut self, fit: Fit) -> Self {
+        self.options.fit = fit;
+        self
+    }
+
+    /// Sets the point of the flame shown at the center of the picture
+    pub fn center(mut self, x: f32, y: f32) -> Self {
+        self.options.center_x = x;
+        self.options.center_y = y;
+        self
+    }
+
+    /// Sets the magnification of the flame, relative to the framing given by `fit`
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.options.scale = scale;
+        self
+    }
+
+    /// Sets the magnification of the flame as a power of two
+    pub fn zoom(mut self, zoom: f32) -> Self {
+        self.options.zoom = zoom;
+        self
+    }
+
+    /// Sets the rotation of the camera in degrees
+    pub fn rotate(mut self, degrees: f32) -> Self {
+        self.options.rotate = degrees;
+        self
+    }
+
+    /// Sets the number of cells of the accumulation grid along each pixel of the picture
+    pub fn supersample(mut self, supersample: u32) -> Self {
+        self.options.supersample = supersample;
+        self
+    }
+
+    /// Sets the shape of the reconstruction filter
+    pub fn filter_shape(mut self, shape: FilterShape) -> Self {
+        self.options.filter_shape = shape;
+        self
+    }
+
+    /// Sets the size in pixels of one unit of the reconstruction filter shape, as flam3's `filter`
+    pub fn filter_radius(mut self, radius: f32) -> Self {
+        self.options.filter_radius = radius;
+        self
+    }
+
+    /// Sets the number of iterations of the chaos game
+    pub fn iterations(mut self, iterations: u64) -> Self {
+        self.options.iterations = iterations;
+        self.samples_per_pixel = None;
+        self
+    }
+
+    /// Sets the number of iterations as an average number of samples per pixel
+    pub fn samples_per_pixel(mut self, samples_per_pixel: f32) -> Self {
+        self.samples_per_pixel = Some(samples_per_pixel);
+        self
+    }
+
+    /// Sets the number of xforms of the random flames
+    pub fn num_xforms(mut self, num_xforms: usize) -> Self {
+        self.options.num_xforms = num_xforms;
+        self
+    }
+
+    /// Sets the names of the variations the random flames are made of
+    pub fn variations<S: AsRef<str>>(mut self, names: &[S]) -> Self {
+        self.options.variations = names.iter().map(|name| name.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Sets the gamma used to correct the colors of the picture
+    pub fn gamma(mut self, gamma: f32) -> Self {
+        self.options.gamma = gamma;
+        self
+    }
+
+    /// Sets the alpha below which the gamma correction is linear
+    pub fn gamma_threshold(mut self, gamma_threshold: f32) -> Self {
+        self.options.gamma_threshold = gamma_threshold;
+        self
+    }
+
+    /// Sets the brightness of the picture
+    pub fn brightness(mut self, brightness: f32) -> Self {
+        self.options.brightness = brightness;
+        self
+    }
+
+    /// Sets the contrast of the picture
+    pub fn contrast(mut self, contrast: f32) -> Self {
+        self.options.contrast = contrast;
+        self
+    }
+
+    /// Sets the blend between correcting the colors with the alpha and correcting each channel
+    pub fn vibrancy(mut self, vibrancy: f32) -> Self {
+        self.options.vibrancy = vibrancy;
+        self
+    }
+
+    /// Sets the largest radius of the density estimation kernel. Zero disables density
+    /// estimation.
+    pub fn estimator_radius(mut self, radius: f32) -> Self {
+        self.options.estimator_radius = radius;
+        self
+    }
+
+    /// Sets the smallest radius of the density estimation kernel
+    pub fn estimator_minimum(mut self, minimum: f32) -> Self {
+        self.options.estimator_minimum = minimum;
+        self
+    }
+
+    /// Sets how fast the radius of the density estimation kernel shrinks with the hits
+    pub fn estimator_curve(mut self, curve: f32) -> Self {
+        self.options.estimator_curve = curve;
+        self
+    }
+
+    /// Sets the number of iterations run before plotting points
+    pub fn fuse(mut self, fuse: u32) -> Self {
+        self.options.fuse = fuse;
+        self
+    }
+
+    /// Sets the built-in palette used instead of the palette of the flame, or `None` to keep
+    /// the palette of the flame
+    pub fn palette(mut self, number: Option<u32>) -> Self {
+        self.options.palette = number;
+        self
+    }
+
+    /// Sets the rotation of the hues of the palette, in turns of the color wheel
+    pub fn hue(mut self, turns: f32) -> Self {
+        self.options.hue = turns;
+        self
+    }
+
+    /// Sets whether the palette is used in reverse order
+    pub fn reverse_palette(mut self, reverse: bool) -> Self {
+        self.options.reverse_palette = reverse;
+        self
+    }
+
+    /// Validates and returns the settings
+    pub fn build(self) -> Result<RenderOptions, OptionsError> {
+        let mut options = self.options;
+        if let Some(spp) = self.samples_per_pixel {
+            if !(spp.is_finite() && spp > 0.0) {
+                return Err(OptionsError::InvalidSamplesPerPixel(spp));
+            }
+            let pixels = options.width as f64 * options.height as f64;
+            options.iterations = (spp as f64 * pixels).round() as u64;
+        }
+        options.validate()?;
+        Ok(options)
+    }
+}
@@ -0,0 +1,662 @@
+//! Palettes: built-in palettes, palette files and palette adjustments
+//!
+//! A palette is a list of colors indexed by the color index of the points, usually
+//! `PALETTE_SIZE` of them. Palettes can be
+//! - picked from the built-in palettes, by number or by name,
+//! - read from the `flam3-palettes.xml` collection distributed with flam3, from Apophysis and
+//!   Ultra Fractal `.ugr` gradients, from Fractint `.map` files and from GIMP `.ggr` gradients,
+//! - extracted from a picture, by clustering its pixels with median cut and chaining the
+//!   clusters from the darkest one to the closest remaining one,
+//! - rotated along the color wheel and reversed.
+//!
+//! The built-in palettes are this crate's own, numbered from 0 and unrelated to the numbers of
+//! the flam3 collection. They are embedded from `palettes.xml`, which is in the format of the
+//! flam3 collection, and read with `parse_flam3_palettes` the first time they are used.
+
+use std::fmt;
+use std::sync::OnceLock;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use super::genome::{Color, PALETTE_SIZE};
+use super::options::RenderOptions;
+
+/// Number of clusters of the pixels of a picture, which are the keys of its palette
+const IMAGE_KEYS: usize = 16;
+/// Largest number of pixels of a picture used to compute its palette, so that large pictures
+/// are sampled
+const IMAGE_SAMPLES: usize = 1 << 16;
+
+/// Built-in palettes, in the format of the `flam3-palettes.xml` collection of flam3
+const BUILTIN: &str = include_str!("palettes.xml");
+
+/// Palette read from a file, with its name when the file gives one
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedPalette {
+    pub name: String,
+    pub colors: Vec<Color>,
+}
+
+/// Errors found while reading a palette file
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The flam3 palette file is not valid XML
+    Xml(String),
+    /// The file does not hold any palette
+    NoPalette,
+    /// A line or an entry of the file cannot be read
+    Invalid(String),
+    /// The picture cannot be decoded or has no opaque pixel
+    Image(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Xml(msg) => write!(f, "invalid XML: {}", msg),
+            Error::NoPalette => write!(f, "no palette found"),
+            Error::Invalid(msg) => write!(f, "invalid palette: {}", msg),
+            Error::Image(msg) => write!(f, "invalid picture: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(feature = "wasm")]
+impl From<Error> for JsValue {
+    fn from(err: Error) -> Self {
+        js_sys::Error::new(&err.to_string()).into()
+    }
+}
+
+/// Returns the built-in palettes with their numbers, parsed the first time they are used
+fn builtin_palettes() -> &'static [(u32, NamedPalette)] {
+    static PALETTES: OnceLock<Vec<(u32, NamedPalette)>> = OnceLock::new();
+    PALETTES.get_or_init(|| parse_numbered(BUILTIN).expect("built-in palettes are valid"))
+}
+
+/// Returns the names of the built-in palettes, in the order of their numbers
+pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+    builtin_palettes()
+        .iter()
+        .map(|(_, palette)| palette.name.as_str())
+}
+
+/// Returns the built-in palette numbered `number`
+pub fn builtin(number: u32) -> Option<Vec<Color>> {
+    builtin_palettes()
+        .iter()
+        .find(|(known, _)| *known == number)
+        .map(|(_, palette)| palette.colors.clone())
+}
+
+/// Returns the number of the built-in palette called `name`
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = palette_index))]
+pub fn builtin_index(name: &str) -> Option<u32> {
+    builtin_palettes()
+        .iter()
+        .find(|(_, palette)| palette.name == name)
+        .map(|(number, _)| *number)
+}
+
+/// Returns the number of built-in palettes
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = palette_count))]
+pub fn builtin_count() -> u32 {
+    builtin_palettes().len() as u32
+}
+
+/// Returns the name of the built-in palette numbered `number`
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = palette_name))]
+pub fn builtin_name(number: u32) -> Option<String> {
+    builtin_palettes()
+        .iter()
+        .find(|(known, _)| *known == number)
+        .map(|(_, palette)| palette.name.clone())
+}
+
+/// Returns a palette of `PALETTE_SIZE` colors going through `keys`, evenly spread
+pub fn gradient(keys: &[Color]) -> Vec<Color> {
+    match keys.len() {
+        0 => vec![(1.0, 1.0, 1.0); PALETTE_SIZE],
+        1 => vec![keys[0]; PALETTE_SIZE],
+        _ => (0..PALETTE_SIZE)
+            .map(|idx| {
+                let position = idx as f32 / (PALETTE_SIZE - 1) as f32 * (keys.len() - 1) as f32;
+                let first = (position as usize).min(keys.len() - 2);
+                mix(keys[first], keys[first + 1], position - first as f32)
+            })
+            .collect(),
+    }
+}
+
+/// Returns the color of `palette` at color index `color`, in [0, 1]
+pub fn color_at(palette: &[Color], color: f32) -> Color {
+    if palette.is_empty() {
+        return (1.0, 1.0, 1.0);
+    }
+    let idx = (color * palette.len() as f32) as usize;
+    palette[idx.min(palette.len() - 1)]
+}
+
+/// Returns the palette used to draw a flame whose own palette is `palette`: the built-in
+/// palette selected by `options`, if any, rotated by its hue and reversed if requested
+pub fn select(palette: &[Color], options: &RenderOptions) -> Vec<Color> {
+    let mut palette = options
+        .palette
+        .and_then(builtin)
+        .unwrap_or_else(|| palette.to_vec());
+    if options.hue != 0.0 {
+        palette = rotate_hue(&palette, options.hue);
+    }
+    if options.reverse_palette {
+        palette.reverse();
+    }
+    palette
+}
+
+/// Returns the palette with the hue of every color rotated by `turns` turns of the color wheel
+pub fn rotate_hue(palette: &[Color], turns: f32) -> Vec<Color> {
+    palette
+        .iter()
+        .map(|&color| {
+            let (hue, saturation, value) = rgb_to_hsv(color);
+            hsv_to_rgb(((hue + turns * 6.0) % 6.0 + 6.0) % 6.0, saturation, value)
+        })
+        .collect()
+}
+
+/// Reads the palettes of the `flam3-palettes.xml` collection distributed with flam3, made of
+/// `<palette number="..." name="..." data="..."/>` elements with the colors as hex `00RRGGBB`
+pub fn parse_flam3_palettes(xml: &str) -> Result<Vec<NamedPalette>, Error> {
+    Ok(parse_numbered(xml)?
+        .into_iter()
+        .map(|(_, palette)| palette)
+        .collect())
+}
+
+/// Reads the palettes of a flam3 collection with their numbers. Palettes without a number are
+/// numbered by their position in the file.
+fn parse_numbered(xml: &str) -> Result<Vec<(u32, NamedPalette)>, Error> {
+    let doc = roxmltree::Document::parse(xml).map_err(|err| Error::Xml(err.to_string()))?;
+    let palettes = doc
+        .descendants()
+        .filter(|node| node.has_tag_name("palette"))
+        .enumerate()
+        .map(|(position, node)| {
+            let number = match node.attribute("number") {
+                Some(number) => number
+                    .trim()
+                    .parse()
+                    .map_err(|_| Error::Invalid(format!("palette number {:?}", number)))?,
+                None => position as u32,
+            };
+            let name = node
+                .attribute("name")
+                .map_or_else(|| number.to_string(), str::to_string);
+            let data: Vec<u8> = node
+                .attribute("data")
+                .unwrap_or("")
+                .bytes()
+                .filter(|byte| !byte.is_ascii_whitespace())
+                .collect();
+            if data.is_empty() || !data.len().is_multiple_of(8) {
+                return Err(Error::Invalid(format!("data of palette {:?}", name)));
+            }
+            let colors = data
+                .chunks(8)
+                .map(|color| {
+                    let channel = |idx: usize| hex_channel(&color[idx..idx + 2]);
+                    Ok((channel(2)?, channel(4)?, channel(6)?))
+                })
+                .collect::<Result<Vec<Color>, Error>>()?;
+            Ok((number, NamedPalette { name, colors }))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    if palettes.is_empty() {
+        Err(Error::NoPalette)
+    } else {
+        Ok(palettes)
+    }
+}
+
+/// Reads the gradients of an Apophysis or Ultra Fractal `.ugr` file. Each gradient is a block
+/// `name { gradient: title="..." ... index=0 color=... ... }` where the colors are `r + 256 g +
+/// 65536 b` at indices in [0, 400). Quoted values, such as titles, can hold spaces and braces.
+pub fn parse_ugr(text: &str) -> Result<Vec<NamedPalette>, Error> {
+    let mut palettes = Vec::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        let close = closing_brace(&rest[open..])
+            .map(|close| open + close)
+            .ok_or_else(|| Error::Invalid("unterminated gradient".to_string()))?;
+        let block_name = rest[..open].lines().last().unwrap_or("").trim();
+        let body = &rest[open + 1..close];
+        rest = &rest[close + 1..];
+
+        let mut name = block_name.to_string();
+        let mut keys: Vec<(f32, Color)> = Vec::new();
+        let mut index = None;
+        for (key, value) in ugr_pairs(body)? {
+            match key {
+                "title" => name = value.to_string(),
+                "index" => index = Some(parse_number::<f32>(value, "ugr index")?),
+                "color" => {
+                    let value = parse_number::<u32>(value, "ugr color")?;
+                    let index = index
+                        .take()
+                        .ok_or_else(|| Error::Invalid("ugr color without index".to_string()))?;
+                    let channel = |shift: u32| ((value >> shift) & 0xFF) as f32 / 255.0;
+                    keys.push((index / 400.0, (channel(0), channel(8), channel(16))));
+                }
+                _ => (),
+            }
+        }
+        if keys.is_empty() {
+            return Err(Error::Invalid(format!("gradient {:?} has no color", name)));
+        }
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        palettes.push(NamedPalette {
+            name,
+            colors: interpolate(&keys),
+        });
+    }
+    if palettes.is_empty() {
+        Err(Error::NoPalette)
+    } else {
+        Ok(palettes)
+    }
+}
+
+/// Returns the position of the `}` closing a `.ugr` gradient, skipping the quoted values
+fn closing_brace(text: &str) -> Option<usize> {
+    let mut quoted = false;
+    text.char_indices()
+        .find(|&(_, c)| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            c == '}' && !quoted
+        })
+        .map(|(idx, _)| idx)
+}
+
+/// Splits the body of a `.ugr` gradient into its `key=value` pairs, where a value in double
+/// quotes can hold spaces. Words that are not pairs, such as `gradient:`, are left out.
+fn ugr_pairs(body: &str) -> Result<Vec<(&str, &str)>, Error> {
+    let mut pairs = Vec::new();
+    let mut rest = body.trim_start();
+    while !rest.is_empty() {
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        let key = &rest[..end];
+        rest = &rest[end..];
+        if let Some(value) = rest.strip_prefix('=') {
+            let (value, after) = match value.strip_prefix('"') {
+                Some(quoted) => {
+                    let end = quoted.find('"').ok_or_else(|| {
+                        Error::Invalid(format!("unterminated ugr value of `{}`", key))
+                    })?;
+                    (&quoted[..end], &quoted[end + 1..])
+                }
+                None => value.split_at(value.find(char::is_whitespace).unwrap_or(value.len())),
+            };
+            pairs.push((key, value));
+            rest = after;
+        }
+        rest = rest.trim_start();
+    }
+    Ok(pairs)
+}
+
+/// Reads a Fractint `.map` file: one `r g b` line per color, with channels in [0, 255] and
+/// anything after them ignored
+pub fn parse_map(text: &str) -> Result<Vec<Color>, Error> {
+    let colors = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut channels = line.split_whitespace().map(|value| {
+                parse_number::<f32>(value, "map channel").map(|channel| channel / 255.0)
+            });
+            match (channels.next(), channels.next(), channels.next()) {
+                (Some(r), Some(g), Some(b)) => Ok((r?, g?, b?)),
+                _ => Err(Error::Invalid(format!("map line {:?}", line))),
+            }
+        })
+        .collect::<Result<Vec<Color>, Error>>()?;
+    if colors.is_empty() {
+        return Err(Error::NoPalette);
+    }
+    // Spread shorter maps over the whole palette
+    let keys: Vec<(f32, Color)> = colors
+        .iter()
+        .enumerate()
+        .map(|(idx, &color)| (idx as f32 / (colors.len() - 1).max(1) as f32, color))
+        .collect();
+    Ok(interpolate(&keys))
+}
+
+/// Reads a GIMP `.ggr` gradient, made of segments with a left, middle and right position, the
+/// RGBA colors at both ends, a blending function and a coloring mode
+pub fn parse_ggr(text: &str) -> Result<NamedPalette, Error> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    if lines.next() != Some("GIMP Gradient") {
+        return Err(Error::Invalid("missing `GIMP Gradient` header".to_string()));
+    }
+    let mut line = lines.next().unwrap_or("");
+    let mut name = String::new();
+    if let Some(rest) = line.strip_prefix("Name:") {
+        name = rest.trim().to_string();
+        line = lines.next().unwrap_or("");
+    }
+    let count: usize = parse_number(line, "ggr segment count")?;
+    let segments = lines
+        .take(count)
+        .map(|line| {
+            let values = line
+                .split_whitespace()
+                .map(|value| parse_number::<f32>(value, "ggr segment"))
+                .collect::<Result<Vec<f32>, Error>>()?;
+            if values.len() < 13 {
+                return Err(Error::Invalid(format!("ggr segment {:?}", line)));
+            }
+            Ok(values)
+        })
+        .collect::<Result<Vec<Vec<f32>>, Error>>()?;
+    if segments.len() != count || count == 0 {
+        return Err(Error::NoPalette);
+    }
+
+    let colors = (0..PALETTE_SIZE)
+        .map(|idx| {
+            let position = (idx as f32 + 0.5) / PALETTE_SIZE as f32;
+            let segment = segments
+                .iter()
+                .find(|segment| position <= segment[2])
+                .unwrap_or(&segments[segments.len() - 1]);
+            ggr_color(segment, position)
+        })
+        .collect();
+    Ok(NamedPalette { name, colors })
+}
+
+/// Returns a palette of `PALETTE_SIZE` colors matching the pixels of a picture, given row by
+/// row with `channels` samples per pixel: 3 for RGB, or 4 for RGBA where transparent pixels are
+/// left out
+pub fn from_pixels(pixels: &[u8], channels: usize) -> Result<Vec<Color>, Error> {
+    if channels != 3 && channels != 4 {
+        return Err(Error::Image(format!(
+            "pixels must have 3 or 4 channels, got {}",
+            channels
+        )));
+    }
+    let count = pixels.len() / channels;
+    let step = count.div_ceil(IMAGE_SAMPLES).max(1);
+    let samples: Vec<[u8; 3]> = pixels
+        .chunks_exact(channels)
+        .step_by(step)
+        .filter(|pixel| channels == 3 || pixel[3] > 0)
+        .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+        .collect();
+    if samples.is_empty() {
+        return Err(Error::Image("no opaque pixel".to_string()));
+    }
+    let keys = median_cut(samples, IMAGE_KEYS);
+    Ok(gradient(&chain(keys)))
+}
+
+/// Returns a palette of `PALETTE_SIZE` colors matching a PNG picture
+pub fn from_png(data: &[u8]) -> Result<Vec<Color>, Error> {
+    let image = |err: png::DecodingError| Error::Image(err.to_string());
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(image)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(image)?;
+    let buffer = &buffer[..info.buffer_size()];
+    match info.color_type {
+        png::ColorType::Rgb => from_pixels(buffer, 3),
+        png::ColorType::Rgba => from_pixels(buffer, 4),
+        png::ColorType::Grayscale => {
+            let rgb: Vec<u8> = buffer.iter().flat_map(|&gray| [gray; 3]).collect();
+            from_pixels(&rgb, 3)
+        }
+        png::ColorType::GrayscaleAlpha => {
+            let rgba: Vec<u8> = buffer
+                .chunks_exact(2)
+                .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+                .collect();
+            from_pixels(&rgba, 4)
+        }
+        png::ColorType::Indexed => Err(Error::Image("palette was not expanded".to_string())),
+    }
+}
+
+/// Returns a palette of `PALETTE_SIZE` colors matching the PNG picture at `path`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn from_png_file<P: AsRef<std::path::Path>>(path: P) -> Result<Vec<Color>, Error> {
+    let data = std::fs::read(path).map_err(|err| Error::Image(err.to_string()))?;
+    from_png(&data)
+}
+
+/// Splits the pixels into at most `keys` boxes, each time cutting the box with the widest range
+/// of a channel at its median, and returns the average color of each box
+fn median_cut(pixels: Vec<[u8; 3]>, keys: usize) -> Vec<Color> {
+    // Widest channel of a box and its range
+    let widest = |pixels: &[[u8; 3]]| {
+        (0..3)
+            .map(|channel| {
+                let values = pixels.iter().map(|pixel| pixel[channel]);
+                let range = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+                (channel, range)
+            })
+            .max_by_key(|&(_, range)| range)
+            .unwrap_or((0, 0))
+    };
+    let mut boxes = vec![pixels];
+    while boxes.len() < keys {
+        let (idx, (channel, range)) = boxes
+            .iter()
+            .map(|pixels| widest(pixels))
+            .enumerate()
+            .max_by_key(|&(_, (_, range))| range)
+            .unwrap_or((0, (0, 0)));
+        if range == 0 {
+            break;
+        }
+        let mut pixels = boxes.swap_remove(idx);
+        pixels.sort_unstable_by_key(|pixel| pixel[channel]);
+        let upper = pixels.split_off(pixels.len() / 2);
+        boxes.push(pixels);
+        boxes.push(upper);
+    }
+    boxes
+        .iter()
+        .map(|pixels| {
+            let sum = |channel: usize| {
+                pixels
+                    .iter()
+                    .map(|pixel| pixel[channel] as f32)
+                    .sum::<f32>()
+            };
+            let count = pixels.len() as f32 * 255.0;
+            (sum(0) / count, sum(1) / count, sum(2) / count)
+        })
+        .collect()
+}
+
+/// Orders colors along a smooth path, starting from the darkest one and moving each time to
+/// the closest remaining one
+fn chain(mut colors: Vec<Color>) -> Vec<Color> {
+    let luminance = |(r, g, b): Color| 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let distance = |a: Color, b: Color| {
+        (a.0 - b.0) * (a.0 - b.0) + (a.1 - b.1) * (a.1 - b.1) + (a.2 - b.2) * (a.2 - b.2)
+    };
+    let mut path = Vec::with_capacity(colors.len());
+    let darkest =
+        (0..colors.len()).min_by(|&a, &b| luminance(colors[a]).total_cmp(&luminance(colors[b])));
+    let mut next = darkest;
+    while let Some(idx) = next {
+        let color = colors.swap_remove(idx);
+        path.push(color);
+        next = (0..colors.len())
+            .min_by(|&a, &b| distance(color, colors[a]).total_cmp(&distance(color, colors[b])));
+    }
+    path
+}
+
+/// Color of a `.ggr` segment at `position`
+fn ggr_color(segment: &[f32], position: f32) -> Color {
+    let (left, middle, right) = (segment[0], segment[1], segment[2]);
+    let start = (segment[3], segment[4], segment[5]);
+    let end = (segment[7], segment[8], segment[9]);
+    let (blending, coloring) = (segment[11] as u32, segment[12] as u32);
+
+    // Position relative to the segment, and the middle relative to the segment
+    let width = right - left;
+    let (t, middle) = if width > 0.0 {
+        (
+            ((position - left) / width).clamp(0.0, 1.0),
+            ((middle - left) / width).clamp(0.0, 1.0),
+        )
+    } else {
+        (0.5, 0.5)
+    };
+    // Linear blend, with the middle mapped to one half, that the other blends are built on
+    let linear = if t <= middle {
+        if middle > 0.0 {
+            0.5 * t / middle
+        } else {
+            0.0
+        }
+    } else if middle < 1.0 {
+        0.5 + 0.5 * (t - middle) / (1.0 - middle)
+    } else {
+        1.0
+    };
+    let factor = match blending {
+        // Curved
+        1 => {
+            if middle > 0.0 && middle < 1.0 {
+                libm::powf(t, libm::logf(0.5) / libm::logf(middle))
+            } else {
+                linear
+            }
+        }
+        // Sine
+        2 => libm::sinf(std::f32::consts::PI * linear - std::f32::consts::FRAC_PI_2) / 2.0 + 0.5,
+        // Sphere increasing
+        3 => 1.0 - (1.0 - linear * linear).max(0.0).sqrt(),
+        // Sphere decreasing
+        4 => (1.0 - (1.0 - linear) * (1.0 - linear)).max(0.0).sqrt(),
+        // Step
+        5 => {
+            if t < middle {
+                0.0
+            } else {
+                1.0
+            }
+        }
+        _ => linear,
+    };
+    match coloring {
+        // HSV, counter-clockwise or clockwise
+        1 | 2 => {
+            let (h1, s1, v1) = rgb_to_hsv(start);
+            let (h2, s2, v2) = rgb_to_hsv(end);
+            let mut delta = h2 - h1;
+            if coloring == 1 && delta < 0.0 {
+                delta += 6.0;
+            } else if coloring == 2 && delta > 0.0 {
+                delta -= 6.0;
+            }
+            let hue = ((h1 + delta * factor) % 6.0 + 6.0) % 6.0;
+            hsv_to_rgb(hue, s1 + (s2 - s1) * factor, v1 + (v2 - v1) * factor)
+        }
+        _ => mix(start, end, factor),
+    }
+}
+
+/// Returns a palette of `PALETTE_SIZE` colors interpolating colors at positions in [0, 1],
+/// sorted by position
+fn interpolate(keys: &[(f32, Color)]) -> Vec<Color> {
+    (0..PALETTE_SIZE)
+        .map(|idx| {
+            let position = idx as f32 / (PALETTE_SIZE - 1) as f32;
+            match keys.iter().position(|&(key, _)| key >= position) {
+                None => keys[keys.len() - 1].1,
+                Some(0) => keys[0].1,
+                Some(next) => {
+                    let (from, to) = (keys[next - 1], keys[next]);
+                    let span = to.0 - from.0;
+                    let frac = if span > 0.0 {
+                        (position - from.0) / span
+                    } else {
+                        1.0
+                    };
+                    mix(from.1, to.1, frac)
+                }
+            }
+        })
+        .collect()
+}
+
+fn mix(from: Color, to: Color, frac: f32) -> Color {
+    (
+        from.0 + (to.0 - from.0) * frac,
+        from.1 + (to.1 - from.1) * frac,
+        from.2 + (to.2 - from.2) * frac,
+    )
+}
+
+/// Converts a color to hue in [0, 6), saturation and value
+fn rgb_to_hsv((r, g, b): Color) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let saturation = if max > 0.0 { delta / max } else { 0.0 };
+    let hue = if delta == 0.0 {
+        0.0
+    } else if r == max {
+        (g - b) / delta
+    } else if g == max {
+        2.0 + (b - r) / delta
+    } else {
+        4.0 + (r - g) / delta
+    };
+    (if hue < 0.0 { hue + 6.0 } else { hue }, saturation, max)
+}
+
+/// Converts a hue in [0, 6), saturation and value to a color
+fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> Color {
+    let sector = (hue.floor() as i32).rem_euclid(6);
+    let frac = hue - hue.floor();
+    let p = value * (1.0 - saturation);
+    let q = value * (1.0 - saturation * frac);
+    let t = value * (1.0 - saturation * (1.0 - frac));
+    match sector {
+        0 => (value, t, p),
+        1 => (q, value, p),
+        2 => (p, value, t),
+        3 => (p, q, value),
+        4 => (t, p, value),
+        _ => (value, p, q),
+    }
+}
+
+fn hex_channel(digits: &[u8]) -> Result<f32, Error> {
+    std::str::from_utf8(digits)
+        .ok()
+        .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+        .map(|value| value as f32 / 255.0)
+        .ok_or_else(|| Error::Invalid("invalid hex digit".to_string()))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, what: &str) -> Result<T, Error> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| Error::Invalid(format!("{} {:?}", what, value)))
+}
@@ -1,8 +1,9 @@
+use super::rng::Rng;
 use super::variations::*;
 use std::ops::{Add, AddAssign, Mul};
 
 /// Representation of a Point
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
 pub struct Point(pub f32, pub f32);
 
 impl Point {
@@ -10,6 +11,11 @@ impl Point {
         Point(0.0, 0.0)
     }
 
+    /// Returns whether both coordinates are neither NaN nor infinite
+    pub fn is_finite(&self) -> bool {
+        self.0.is_finite() && self.1.is_finite()
+    }
+
     /// Applies affine transformation on a point
     pub fn affine(&self, coeffs: (f32, f32, f32, f32, f32, f32)) -> Point {
         let Point(x, y) = self;
@@ -17,13 +23,18 @@ impl Point {
         Point(a * x + b * y + c, d * x + e * y + f)
     }
 
-    /// Applies a function on a point given the weights and variations that define such function
-    pub fn apply_variation(p: &PreProc, weights: &Vec<f32>, vars: &Vec<fn(&PreProc) -> Point>) -> Point {
+    /// Applies a function on a point given the variations that define such function and their
+    /// weights
+    pub fn apply_variation(
+        p: &PreProc,
+        variations: &[WeightedVariation],
+        rng: &mut dyn Rng,
+    ) -> Point {
         let mut res = Point::new();
-        for i in 0..vars.len() {
-            res += weights[i] * vars[i](p);
+        for (variation, weight) in variations {
+            res += variation.apply_weighted(p, *weight, rng);
         }
-        return res;
+        res
     }
 }
 
@@ -39,10 +50,7 @@ impl Add for Point {
 
 impl AddAssign for Point {
     fn add_assign(&mut self, other: Self) {
-        *self = Self {
-            0: self.0 + other.0,
-            1: self.1 + other.1,
-        }
+        *self = Self(self.0 + other.0, self.1 + other.1)
     }
 }
 
@@ -0,0 +1,106 @@
+//! Random number generation
+//!
+//! Every random choice made while creating or rendering a flame goes through the [`Rng`] trait,
+//! so that a flame can be reproduced exactly from its seed on any target. The transcendental
+//! functions of the chaos game and of the tone mapping come from `libm` instead of the platform
+//! for the same reason.
+
+/// Source of uniformly distributed random numbers
+pub trait Rng {
+    /// Returns the next random 32 bits
+    fn next_u32(&mut self) -> u32;
+
+    /// Returns the next random 64 bits
+    fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+
+    /// Returns a random number in [0, 1)
+    fn next_f32(&mut self) -> f32 {
+        // Only keep as many bits as fit in the mantissa so that the result is never rounded up
+        // to 1.0
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Returns a random number in [low, high)
+    fn range(&mut self, low: f32, high: f32) -> f32 {
+        low + (high - low) * self.next_f32()
+    }
+
+    /// Returns a random integer in [0, n)
+    fn below(&mut self, n: u32) -> u32 {
+        ((self.next_u32() as u64 * n as u64) >> 32) as u32
+    }
+}
+
+/// Default seedable generator: PCG-XSH-RR 64/32 (O'Neill, 2014)
+///
+/// It only uses integer arithmetic, so the same seed produces the same sequence on wasm and native
+/// targets.
+#[derive(Debug, Clone)]
+pub struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+const PCG_MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+const PCG_DEFAULT_STREAM: u64 = 1_442_695_040_888_963_407;
+
+impl Pcg32 {
+    /// Creates a generator from a seed
+    pub fn new(seed: u64) -> Pcg32 {
+        Pcg32::with_stream(seed, PCG_DEFAULT_STREAM)
+    }
+
+    /// Creates a generator from a seed and a stream selector. Generators with the same seed but
+    /// different streams produce independent sequences.
+    pub fn with_stream(seed: u64, stream: u64) -> Pcg32 {
+        let mut rng = Pcg32 {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(PCG_MULTIPLIER)
+            .wrapping_add(self.inc);
+    }
+}
+
+impl Rng for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+}
+
+/// Returns a seed taken from the environment, for when reproducibility is not needed
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub fn random_seed() -> u64 {
+    let high = (js_sys::Math::random() * 4_294_967_296.0) as u64;
+    let low = (js_sys::Math::random() * 4_294_967_296.0) as u64;
+    (high << 32) | low
+}
+
+/// Returns a seed taken from the environment, for when reproducibility is not needed
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub fn random_seed() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or(0);
+    // Spread the low-entropy timestamp over all the bits of the seed
+    let mut rng = Pcg32::new(nanos);
+    ((rng.next_u32() as u64) << 32) | rng.next_u32() as u64
+}
@@ -0,0 +1,85 @@
+//! Tone mapping of the hits of the chaos game into colors, following flam3
+//!
+//! 1. Log density: each cell is scaled by `k1 * ln(1 + k2 * hits) / hits`, where `k2` makes the
+//!    density relative to the average number of samples per cell and `k1` applies the brightness
+//!    and contrast. The alpha of the cell becomes `k1 * ln(1 + k2 * hits)`. Unless
+//!    `estimator_radius` is zero, the scaled cells are then spread by density estimation, and
+//!    the reconstruction filter averages them into the pixels of the picture.
+//! 2. Gamma: the alpha is gamma corrected, linearly below `gamma_threshold` so that faint cells
+//!    are not amplified into noise.
+//! 3. Vibrancy: the colors are blended between scaling by the corrected alpha, which keeps hues,
+//!    and correcting each channel on its own.
+
+use super::density::{self, Accumulation};
+use super::filter;
+use super::histogram::{Histogram, COLOR_ONE};
+use super::options::RenderOptions;
+
+/// Same brightness as flam3 for the same settings, where a color channel of 1 is 256/268
+const BRIGHTNESS_SCALE: f64 = 268.0 / 256.0;
+
+/// Computes the alpha and the color of every cell from the hits of `histogram`, where `samples`
+/// is the number of iterations that produced them
+pub fn tone_map(
+    histogram: &Histogram,
+    options: &RenderOptions,
+    samples: u64,
+    cell_alpha: &mut [f32],
+    cell_color: &mut [(f32, f32, f32)],
+) {
+    let contrast = options.contrast as f64;
+    let k1 = contrast * options.brightness as f64 * BRIGHTNESS_SCALE;
+    let cells = histogram.counter.len();
+    let k2 = cells as f64 / (contrast * samples.max(1) as f64);
+
+    let gamma = 1.0 / options.gamma as f64;
+    let vibrancy = options.vibrancy as f64;
+    let threshold = options.gamma_threshold as f64;
+    let threshold_value = libm::pow(threshold, gamma);
+    let correct = |value: f64| {
+        if value < threshold {
+            let frac = value / threshold;
+            (1.0 - frac) * value * threshold_value / threshold + frac * libm::pow(value, gamma)
+        } else {
+            libm::pow(value, gamma)
+        }
+    };
+
+    let log_scale = |hits: f64| k1 * libm::log(1.0 + k2 * hits) / hits;
+    let mut accumulation: Vec<Accumulation> = vec![(0.0, 0.0, 0.0, 0.0); cells];
+    if options.estimator_radius > 0.0 {
+        density::estimate(histogram, options, log_scale, &mut accumulation);
+    } else {
+        let cells = histogram.counter.iter().zip(&histogram.color);
+        for (cell, (&hits, &color)) in accumulation.iter_mut().zip(cells) {
+            if hits > 0 {
+                let scale = log_scale(hits as f64);
+                let channel = |sum: u64| sum as f64 / COLOR_ONE as f64 * scale;
+                *cell = (
+                    channel(color.0),
+                    channel(color.1),
+                    channel(color.2),
+                    hits as f64 * scale,
+                );
+            }
+        }
+    }
+
+    let pixels = filter::downsample(&accumulation, options);
+
+    for (idx, &(red, green, blue, alpha)) in pixels.iter().enumerate() {
+        if alpha <= 0.0 {
+            cell_alpha[idx] = 0.0;
+            cell_color[idx] = (0.0, 0.0, 0.0);
+            continue;
+        }
+        let corrected = correct(alpha);
+        let alpha_scale = vibrancy * corrected / alpha;
+        let channel = |value: f64| {
+            let value = alpha_scale * value + (1.0 - vibrancy) * libm::pow(value, gamma);
+            value.clamp(0.0, 1.0) as f32
+        };
+        cell_alpha[idx] = corrected.clamp(0.0, 1.0) as f32;
+        cell_color[idx] = (channel(red), channel(green), channel(blue));
+    }
+}
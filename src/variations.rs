@@ -1,48 +1,708 @@
 //! This module defines the variations that can be applied to a Point
 //!
-//! All these function have been taken from Draves (2003)
+//! All these function have been taken from Draves (2003), and cover its appendix from linear
+//! (v0) to cross (v48). Where the paper leaves details out (random parameters, divisions by zero)
+//! they follow flam3.
+//!
+//! Each variation implements `Variation`. Parametric variations own their parameters, named as
+//! in flam3 files, with a default value and the range their random values are drawn from, so a
+//! new one only needs a struct declared with `parametric!` and its formula. `VARIATIONS` is the
+//! registry of the implemented variations, from which instances are created by name.
 
 use super::point::*;
+use super::rng::Rng;
 
-#[derive(Debug, Copy, Clone)]
-struct Blob {
-    high: f32,
-    low: f32,
-    waves: f32,
+use std::fmt;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// Signature of the variations without parameters. Variations that have a random component
+/// (such as julia) draw from the given generator.
+pub type VariationFn = fn(&PreProc, &mut dyn Rng) -> Point;
+
+/// Signature of the variations without parameters that use their weight inside their formula.
+/// The result is already scaled by the weight.
+pub type WeightedFn = fn(&PreProc, f32, &mut dyn Rng) -> Point;
+
+/// Small value added by flam3 to avoid divisions by zero
+const EPS: f32 = 1e-10;
+
+/// A variation of an xform and its weight
+pub type WeightedVariation = (Box<dyn Variation>, f32);
+
+/// Parameter of a variation
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ParamInfo {
+    /// Name of the parameter in flam3 files
+    pub name: &'static str,
+    /// Value of the parameter when a flam3 file does not give it
+    pub default: f32,
+    /// Random values are drawn in [min, max)
+    pub min: f32,
+    pub max: f32,
+    /// Whether random values are rounded down to integers
+    pub integer: bool,
 }
 
-#[derive(Debug, Copy, Clone)]
-struct Pdj {
-    a: f32,
-    b: f32,
-    c: f32,
-    d: f32,
+/// A variation of the flame, along with the values of its parameters
+pub trait Variation: fmt::Debug + Send + Sync {
+    /// Returns the name of the variation in flam3 files
+    fn name(&self) -> &'static str;
+
+    /// Applies the variation on a point
+    fn apply(&self, p: &PreProc, rng: &mut dyn Rng) -> Point;
+
+    /// Applies the variation on a point and scales it by `weight`. A few variations of flam3
+    /// (such as arch or radial_blur) also use their weight inside their formula.
+    fn apply_weighted(&self, p: &PreProc, weight: f32, rng: &mut dyn Rng) -> Point {
+        weight * self.apply(p, rng)
+    }
+
+    /// Returns the parameters of the variation, empty when it is not parametric
+    fn params(&self) -> &'static [ParamInfo] {
+        &[]
+    }
+
+    /// Returns the value of the parameter called `name`
+    fn param(&self, _name: &str) -> Option<f32> {
+        None
+    }
+
+    /// Sets the value of the parameter called `name`. Returns false when the variation has no
+    /// such parameter.
+    fn set_param(&mut self, _name: &str, _value: f32) -> bool {
+        false
+    }
+
+    /// Draws random values for the parameters
+    fn randomize(&mut self, rng: &mut dyn Rng) {
+        for param in self.params() {
+            let value = rng.range(param.min, param.max);
+            let value = if param.integer { value.floor() } else { value };
+            self.set_param(param.name, value);
+        }
+    }
+
+    /// Returns a copy of the variation
+    fn clone_box(&self) -> Box<dyn Variation>;
 }
 
-#[derive(Debug, Copy, Clone)]
-struct Fan {
-    x: f32,
-    y: f32,
+impl Clone for Box<dyn Variation> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+impl PartialEq for dyn Variation {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+            && self
+                .params()
+                .iter()
+                .all(|param| self.param(param.name) == other.param(param.name))
+    }
 }
 
+/// Variation without parameters
 #[derive(Debug, Copy, Clone)]
-struct Curl {
-    c1: f32,
-    c2: f32,
+pub struct Simple {
+    name: &'static str,
+    function: VariationFn,
+}
+
+impl Variation for Simple {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn apply(&self, p: &PreProc, rng: &mut dyn Rng) -> Point {
+        (self.function)(p, rng)
+    }
+
+    fn clone_box(&self) -> Box<dyn Variation> {
+        Box::new(*self)
+    }
 }
 
+/// Variation without parameters that uses its weight inside its formula
 #[derive(Debug, Copy, Clone)]
-pub struct Params {
-    blob: Blob,
-    pdj: Pdj,
-    fan: Fan,
-    curl: Curl,
+pub struct Weighted {
+    name: &'static str,
+    function: WeightedFn,
+}
+
+impl Variation for Weighted {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn apply(&self, p: &PreProc, rng: &mut dyn Rng) -> Point {
+        (self.function)(p, 1.0, rng)
+    }
+
+    fn apply_weighted(&self, p: &PreProc, weight: f32, rng: &mut dyn Rng) -> Point {
+        (self.function)(p, weight, rng)
+    }
+
+    fn clone_box(&self) -> Box<dyn Variation> {
+        Box::new(*self)
+    }
+}
+
+/// Declares a parametric variation: a struct with one field per parameter, and its
+/// implementation of `Variation` applying `$function(&self, p, rng)`, or
+/// `$function(&self, p, weight, rng)` for the `weighted` variations
+macro_rules! parametric {
+    (
+        $(#[$doc:meta])*
+        $variation:ident($name:literal, $function:ident) { $($params:tt)+ }
+    ) => {
+        parametric! {
+            @define $(#[$doc])* $variation($name) { $($params)+ }
+            fn apply(&self, p: &PreProc, rng: &mut dyn Rng) -> Point {
+                $function(self, p, rng)
+            }
+        }
+    };
+    (
+        $(#[$doc:meta])*
+        $variation:ident($name:literal, $function:ident, weighted) { $($params:tt)+ }
+    ) => {
+        parametric! {
+            @define $(#[$doc])* $variation($name) { $($params)+ }
+            fn apply(&self, p: &PreProc, rng: &mut dyn Rng) -> Point {
+                $function(self, p, 1.0, rng)
+            }
+
+            fn apply_weighted(&self, p: &PreProc, weight: f32, rng: &mut dyn Rng) -> Point {
+                $function(self, p, weight, rng)
+            }
+        }
+    };
+    (
+        @define $(#[$doc:meta])*
+        $variation:ident($name:literal) {
+            $($field:ident: $param:literal, $default:expr, ($min:expr, $max:expr), $integer:expr;)+
+        }
+        $($apply:tt)+
+    ) => {
+        $(#[$doc])*
+        #[derive(Debug, Copy, Clone, PartialEq)]
+        pub struct $variation {
+            $(pub $field: f32,)+
+        }
+
+        impl $variation {
+            const PARAMS: &'static [ParamInfo] = &[$(ParamInfo {
+                name: $param,
+                default: $default,
+                min: $min,
+                max: $max,
+                integer: $integer,
+            },)+];
+        }
+
+        impl Default for $variation {
+            /// Same defaults as flam3
+            fn default() -> Self {
+                $variation {
+                    $($field: $default,)+
+                }
+            }
+        }
+
+        impl Variation for $variation {
+            fn name(&self) -> &'static str {
+                $name
+            }
+
+            $($apply)+
+
+            fn params(&self) -> &'static [ParamInfo] {
+                Self::PARAMS
+            }
+
+            fn param(&self, name: &str) -> Option<f32> {
+                match name {
+                    $($param => Some(self.$field),)+
+                    _ => None,
+                }
+            }
+
+            fn set_param(&mut self, name: &str, value: f32) -> bool {
+                match name {
+                    $($param => self.$field = value,)+
+                    _ => return false,
+                }
+                true
+            }
+
+            fn clone_box(&self) -> Box<dyn Variation> {
+                Box::new(*self)
+            }
+        }
+    };
+}
+
+parametric! {
+    /// Blob (v23)
+    Blob("blob", v23) {
+        low: "blob_low", 0.0, (0.0, 1.0), false;
+        high: "blob_high", 1.0, (1.0, 2.0), false;
+        waves: "blob_waves", 1.0, (0.0, 8.0), true;
+    }
+}
+
+parametric! {
+    /// PDJ (v24)
+    Pdj("pdj", v24) {
+        a: "pdj_a", 0.0, (0.0, 3.0), false;
+        b: "pdj_b", 0.0, (0.0, 3.0), false;
+        c: "pdj_c", 0.0, (0.0, 3.0), false;
+        d: "pdj_d", 0.0, (0.0, 3.0), false;
+    }
+}
+
+parametric! {
+    /// Fan2 (v25)
+    Fan2("fan2", v25) {
+        x: "fan2_x", 0.0, (0.0, 1.0), false;
+        y: "fan2_y", 0.0, (0.0, 1.0), false;
+    }
+}
+
+parametric! {
+    /// Rings2 (v26)
+    Rings2("rings2", v26) {
+        val: "rings2_val", 0.0, (0.0, 2.0), false;
+    }
+}
+
+parametric! {
+    /// Perspective (v30)
+    Perspective("perspective", v30) {
+        angle: "perspective_angle", 0.0, (0.0, 1.0), false;
+        dist: "perspective_dist", 0.0, (1.0, 3.0), false;
+    }
+}
+
+parametric! {
+    /// Julian (v32)
+    Julian("julian", v32) {
+        power: "julian_power", 1.0, (2.0, 7.0), true;
+        dist: "julian_dist", 1.0, (0.5, 2.0), false;
+    }
+}
+
+parametric! {
+    /// JuliaScope (v33)
+    JuliaScope("juliascope", v33) {
+        power: "juliascope_power", 1.0, (2.0, 7.0), true;
+        dist: "juliascope_dist", 1.0, (0.5, 2.0), false;
+    }
+}
+
+parametric! {
+    /// Radial blur (v36), whose blur grows with its weight
+    RadialBlur("radial_blur", v36, weighted) {
+        angle: "radial_blur_angle", 0.0, (-1.0, 1.0), false;
+    }
+}
+
+parametric! {
+    /// Pie (v37)
+    Pie("pie", v37) {
+        slices: "pie_slices", 6.0, (1.0, 10.0), true;
+        rotation: "pie_rotation", 0.0, (0.0, 2.0 * std::f32::consts::PI), false;
+        thickness: "pie_thickness", 0.5, (0.0, 1.0), false;
+    }
+}
+
+parametric! {
+    /// Ngon (v38)
+    Ngon("ngon", v38) {
+        sides: "ngon_sides", 5.0, (3.0, 13.0), true;
+        power: "ngon_power", 3.0, (1.0, 4.0), false;
+        circle: "ngon_circle", 1.0, (0.0, 3.0), false;
+        corners: "ngon_corners", 2.0, (0.0, 2.0), false;
+    }
+}
+
+parametric! {
+    /// Curl (v39)
+    Curl("curl", v39) {
+        c1: "curl_c1", 0.0, (0.0, 1.0), false;
+        c2: "curl_c2", 0.0, (0.0, 1.0), false;
+    }
+}
+
+parametric! {
+    /// Rectangles (v40)
+    Rectangles("rectangles", v40) {
+        x: "rectangles_x", 1.0, (0.0, 1.0), false;
+        y: "rectangles_y", 1.0, (0.0, 1.0), false;
+    }
+}
+
+/// How the registry creates a variation
+#[derive(Copy, Clone)]
+pub enum Kind {
+    /// Variation without parameters
+    Simple(VariationFn),
+    /// Variation without parameters that uses its weight inside its formula
+    Weighted(WeightedFn),
+    /// Parametric variation, created with its default parameters
+    Parametric(fn() -> Box<dyn Variation>),
+}
+
+/// Entry of the registry of variations
+#[derive(Copy, Clone)]
+pub struct VariationInfo {
+    /// Name of the variation in flam3 files
+    pub name: &'static str,
+    /// Number of the variation in flam3, its position in `FLAM3_VARIATIONS`
+    pub id: u32,
+    /// How the variation is created
+    pub kind: Kind,
+    /// Whether the variation takes its parameters from the affine coefficients of the xform
+    pub uses_coeffs: bool,
+    /// Whether the variation draws random numbers
+    pub random: bool,
+}
+
+impl fmt::Debug for VariationInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("VariationInfo")
+            .field("name", &self.name)
+            .field("id", &self.id)
+            .field("parameters", &self.parameters())
+            .field("uses_coeffs", &self.uses_coeffs)
+            .field("random", &self.random)
+            .finish()
+    }
+}
+
+impl VariationInfo {
+    /// Creates the variation, with the default values of its parameters
+    pub fn create(&self) -> Box<dyn Variation> {
+        match self.kind {
+            Kind::Simple(function) => Box::new(Simple {
+                name: self.name,
+                function,
+            }),
+            Kind::Weighted(function) => Box::new(Weighted {
+                name: self.name,
+                function,
+            }),
+            Kind::Parametric(create) => create(),
+        }
+    }
+
+    /// Returns the parameters of the variation
+    pub fn parameters(&self) -> &'static [ParamInfo] {
+        match self.kind {
+            Kind::Simple(_) | Kind::Weighted(_) => &[],
+            Kind::Parametric(create) => create().params(),
+        }
+    }
+
+    /// Returns whether the variation depends on parameters besides the point
+    pub fn is_parametric(&self) -> bool {
+        matches!(self.kind, Kind::Parametric(_)) || self.uses_coeffs
+    }
+}
+
+const fn simple(name: &'static str, id: u32, function: VariationFn) -> VariationInfo {
+    VariationInfo {
+        name,
+        id,
+        kind: Kind::Simple(function),
+        uses_coeffs: false,
+        random: false,
+    }
+}
+
+const fn weighted(name: &'static str, id: u32, function: WeightedFn) -> VariationInfo {
+    VariationInfo {
+        kind: Kind::Weighted(function),
+        ..simple(name, id, v0)
+    }
+}
+
+const fn with_params(
+    name: &'static str,
+    id: u32,
+    create: fn() -> Box<dyn Variation>,
+) -> VariationInfo {
+    VariationInfo {
+        name,
+        id,
+        kind: Kind::Parametric(create),
+        uses_coeffs: false,
+        random: false,
+    }
+}
+
+/// Registry of the implemented variations, in the order of their flam3 ids
+pub const VARIATIONS: &[VariationInfo] = &[
+    simple("linear", 0, v0),
+    simple("sinusoidal", 1, v1),
+    simple("spherical", 2, v2),
+    simple("swirl", 3, v3),
+    simple("horseshoe", 4, v4),
+    simple("polar", 5, v5),
+    simple("handkerchief", 6, v6),
+    simple("heart", 7, v7),
+    simple("disc", 8, v8),
+    simple("spiral", 9, v9),
+    simple("hyperbolic", 10, v10),
+    simple("diamond", 11, v11),
+    simple("ex", 12, v12),
+    VariationInfo {
+        random: true,
+        ..simple("julia", 13, v13)
+    },
+    simple("bent", 14, v14),
+    VariationInfo {
+        uses_coeffs: true,
+        ..simple("waves", 15, v15)
+    },
+    simple("fisheye", 16, v16),
+    VariationInfo {
+        uses_coeffs: true,
+        ..simple("popcorn", 17, v17)
+    },
+    simple("exponential", 18, v18),
+    simple("power", 19, v19),
+    simple("cosine", 20, v20),
+    VariationInfo {
+        uses_coeffs: true,
+        ..simple("rings", 21, v21)
+    },
+    VariationInfo {
+        uses_coeffs: true,
+        ..simple("fan", 22, v22)
+    },
+    with_params("blob", 23, || Box::new(Blob::default())),
+    with_params("pdj", 24, || Box::new(Pdj::default())),
+    with_params("fan2", 25, || Box::new(Fan2::default())),
+    with_params("rings2", 26, || Box::new(Rings2::default())),
+    simple("eyefish", 27, v27),
+    simple("bubble", 28, v28),
+    simple("cylinder", 29, v29),
+    with_params("perspective", 30, || Box::new(Perspective::default())),
+    VariationInfo {
+        random: true,
+        ..simple("noise", 31, v31)
+    },
+    VariationInfo {
+        random: true,
+        ..with_params("julian", 32, || Box::new(Julian::default()))
+    },
+    VariationInfo {
+        random: true,
+        ..with_params("juliascope", 33, || Box::new(JuliaScope::default()))
+    },
+    VariationInfo {
+        random: true,
+        ..simple("blur", 34, v34)
+    },
+    VariationInfo {
+        random: true,
+        ..simple("gaussian_blur", 35, v35)
+    },
+    VariationInfo {
+        random: true,
+        ..with_params("radial_blur", 36, || Box::new(RadialBlur::default()))
+    },
+    VariationInfo {
+        random: true,
+        ..with_params("pie", 37, || Box::new(Pie::default()))
+    },
+    with_params("ngon", 38, || Box::new(Ngon::default())),
+    with_params("curl", 39, || Box::new(Curl::default())),
+    with_params("rectangles", 40, || Box::new(Rectangles::default())),
+    VariationInfo {
+        random: true,
+        ..weighted("arch", 41, v41)
+    },
+    simple("tangent", 42, v42),
+    VariationInfo {
+        random: true,
+        ..simple("square", 43, v43)
+    },
+    VariationInfo {
+        random: true,
+        ..weighted("rays", 44, v44)
+    },
+    VariationInfo {
+        random: true,
+        ..weighted("blade", 45, v45)
+    },
+    weighted("secant2", 46, v46),
+    VariationInfo {
+        random: true,
+        ..weighted("twintrian", 47, v47)
+    },
+    simple("cross", 48, v48),
+];
+
+/// Variations of the random flames unless the render settings choose others. Variations added
+/// later are left out so that a seed keeps giving the same flame.
+pub const DEFAULT_VARIATIONS: &[&str] = &[
+    "sinusoidal",
+    "spherical",
+    "swirl",
+    "horseshoe",
+    "polar",
+    "handkerchief",
+    "disc",
+    "spiral",
+    "hyperbolic",
+    "diamond",
+    "ex",
+    "julia",
+    "bent",
+    "waves",
+    "fisheye",
+    "power",
+    "blob",
+    "pdj",
+    "eyefish",
+    "bubble",
+    "cylinder",
+    "curl",
+    "tangent",
+];
+
+/// Names of all the variations known to flam3, in the order of their flam3 ids
+pub const FLAM3_VARIATIONS: &[&str] = &[
+    "linear",
+    "sinusoidal",
+    "spherical",
+    "swirl",
+    "horseshoe",
+    "polar",
+    "handkerchief",
+    "heart",
+    "disc",
+    "spiral",
+    "hyperbolic",
+    "diamond",
+    "ex",
+    "julia",
+    "bent",
+    "waves",
+    "fisheye",
+    "popcorn",
+    "exponential",
+    "power",
+    "cosine",
+    "rings",
+    "fan",
+    "blob",
+    "pdj",
+    "fan2",
+    "rings2",
+    "eyefish",
+    "bubble",
+    "cylinder",
+    "perspective",
+    "noise",
+    "julian",
+    "juliascope",
+    "blur",
+    "gaussian_blur",
+    "radial_blur",
+    "pie",
+    "ngon",
+    "curl",
+    "rectangles",
+    "arch",
+    "tangent",
+    "square",
+    "rays",
+    "blade",
+    "secant2",
+    "twintrian",
+    "cross",
+    "disc2",
+    "super_shape",
+    "flower",
+    "conic",
+    "parabola",
+    "bent2",
+    "bipolar",
+    "boarders",
+    "butterfly",
+    "cell",
+    "cpow",
+    "curve",
+    "edisc",
+    "elliptic",
+    "escher",
+    "foci",
+    "lazysusan",
+    "loonie",
+    "pre_blur",
+    "modulus",
+    "oscilloscope",
+    "polar2",
+    "popcorn2",
+    "scry",
+    "separation",
+    "split",
+    "splits",
+    "stripes",
+    "wedge",
+    "wedge_julia",
+    "wedge_sph",
+    "whorl",
+    "waves2",
+    "exp",
+    "log",
+    "sin",
+    "cos",
+    "tan",
+    "sec",
+    "csc",
+    "cot",
+    "sinh",
+    "cosh",
+    "tanh",
+    "sech",
+    "csch",
+    "coth",
+    "auger",
+    "flux",
+    "mobius",
+];
+
+/// Returns the position in `VARIATIONS` of the variation called `name`
+pub fn variation_index(name: &str) -> Option<usize> {
+    VARIATIONS
+        .iter()
+        .position(|variation| variation.name == name)
+}
+
+/// Creates the variation called `name`, with the default values of its parameters
+pub fn create_variation(name: &str) -> Option<Box<dyn Variation>> {
+    variation_index(name).map(|idx| VARIATIONS[idx].create())
+}
+
+/// Returns the names of all the implemented variations
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn variation_names() -> Vec<String> {
+    VARIATIONS
+        .iter()
+        .map(|variation| variation.name.to_string())
+        .collect()
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct PreProc {
     coeffs: (f32, f32, f32, f32, f32, f32),
-    params: Params,
     pi: f32,
     x: f32,
     y: f32,
@@ -50,7 +710,11 @@ pub struct PreProc {
     r_inv: f32,
     r2: f32,
     r2_inv: f32,
+    /// Angle of the paper, arctan(x / y), so that sin(theta) = x / r (flam3's `precalc_atan`)
     theta: f32,
+    /// Angle of the point from the x axis, used by the variations that flam3 computes with
+    /// `atan2(y, x)` (julian, juliascope, radial_blur and ngon) instead of `theta`
+    phi: f32,
     sinx: f32,
     siny: f32,
     cosy: f32,
@@ -71,205 +735,169 @@ pub struct PreProc {
     cospy: f32,
 }
 
-impl Params {
-    pub fn new(num_functions: usize) -> Vec<Params> {
-        (0..num_functions)
-            .map(|_| {
-                let low = js_sys::Math::random() as f32;
-                let high = low + js_sys::Math::random() as f32;
-                let waves = js_sys::Math::floor(js_sys::Math::random() * 8.0) as f32;
-                let pdj_a = 3.0 * js_sys::Math::random() as f32;
-                let pdj_b = 3.0 * js_sys::Math::random() as f32;
-                let pdj_c = 3.0 * js_sys::Math::random() as f32;
-                let pdj_d = 3.0 * js_sys::Math::random() as f32;
-                let fanx = js_sys::Math::random() as f32;
-                let fany = js_sys::Math::random() as f32;
-                let curlc1 = js_sys::Math::random() as f32;
-                let curlc2 = js_sys::Math::random() as f32;
-                Params {
-                    blob: Blob {
-                        high: high,
-                        low: low,
-                        waves: waves,
-                    },
-                    pdj: Pdj {
-                        a: pdj_a,
-                        b: pdj_b,
-                        c: pdj_c,
-                        d: pdj_d,
-                    },
-                    fan: Fan { x: fanx, y: fany },
-                    curl: Curl {
-                        c1: curlc1,
-                        c2: curlc2,
-                    },
-                }
-            })
-            .collect::<Vec<Params>>()
-    }
-}
-
-pub fn pre_proc(p: Point, coeffs: (f32, f32, f32, f32, f32, f32), params: &Params) -> PreProc {
+pub fn pre_proc(p: Point, coeffs: (f32, f32, f32, f32, f32, f32)) -> PreProc {
     let Point(x, y) = p;
-    let pi = (-1.0_f32).acos();
+    let pi = libm::acosf(-1.0_f32);
     let r2 = x * x + y * y;
     let r = r2.sqrt();
-    let theta = x.atan2(y);
+    let theta = libm::atan2f(x, y);
     PreProc {
-        coeffs: coeffs,
-        params: *params,
-        pi: pi,
-        x: x,
-        y: y,
-        r: r,
+        coeffs,
+        pi,
+        x,
+        y,
+        r,
         r_inv: 1.0 / r,
-        r2: r2,
+        r2,
         r2_inv: 1.0 / r2,
-        theta: theta,
-        sinx: x.sin(),
-        siny: y.sin(),
-        cosy: y.cos(),
-        tany: y.tan(),
-        sint: theta.sin(),
-        cost: theta.cos(),
-        sinr: r.sin(),
-        cosr: r.sin(),
-        sinr2: r2.sin(),
-        cosr2: r2.cos(),
-        sintr_sum: (theta + r).sin(),
-        costr_sum: (theta - r).cos(),
-        sintr_prod: (theta * r).sin(),
-        costr_prod: (theta * r).sin(),
-        sinpr: (pi * r).sin(),
-        cospr: (pi * r).cos(),
-        sinpy: (pi * y).sin(),
-        cospy: (pi * y).cos(),
+        theta,
+        phi: libm::atan2f(y, x),
+        sinx: libm::sinf(x),
+        siny: libm::sinf(y),
+        cosy: libm::cosf(y),
+        tany: libm::tanf(y),
+        sint: libm::sinf(theta),
+        cost: libm::cosf(theta),
+        sinr: libm::sinf(r),
+        cosr: libm::cosf(r),
+        sinr2: libm::sinf(r2),
+        cosr2: libm::cosf(r2),
+        sintr_sum: libm::sinf(theta + r),
+        costr_sum: libm::cosf(theta - r),
+        sintr_prod: libm::sinf(theta * r),
+        costr_prod: libm::cosf(theta * r),
+        sinpr: libm::sinf(pi * r),
+        cospr: libm::cosf(pi * r),
+        sinpy: libm::sinf(pi * y),
+        cospy: libm::cosf(pi * y),
     }
 }
+// Linear
+pub fn v0(p: &PreProc, _rng: &mut dyn Rng) -> Point {
+    Point(p.x, p.y)
+}
+
 // Sinusoidal
-pub fn v1(p: &PreProc) -> Point {
+pub fn v1(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     Point(p.sinx, p.siny)
 }
 
 // Spherical
-pub fn v2(p: &PreProc) -> Point {
+pub fn v2(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     p.r2_inv * Point(p.x, p.y)
 }
 
 // Swirl
-pub fn v3(p: &PreProc) -> Point {
+pub fn v3(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     Point(p.x * p.sinr2 - p.y * p.cosr2, p.x * p.cosr2 + p.y * p.sinr2)
 }
 
 // Horseshoe
-pub fn v4(p: &PreProc) -> Point {
+pub fn v4(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     p.r_inv * Point((p.x - p.y) * (p.x + p.y), 2.0 * p.x * p.y)
 }
 
 // Polar
-pub fn v5(p: &PreProc) -> Point {
+pub fn v5(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     Point(p.theta / p.pi, p.r - 1.0)
 }
 
 // Handkerchief
-pub fn v6(p: &PreProc) -> Point {
+pub fn v6(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     Point(p.r * p.sintr_sum, p.r * p.costr_sum)
 }
 
 // Heart
-#[allow(unused)]
-pub fn v7(p: &PreProc) -> Point {
+pub fn v7(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     Point(p.r * p.sintr_prod, -p.r * p.costr_prod)
 }
 
 // Disc
-pub fn v8(p: &PreProc) -> Point {
+pub fn v8(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     (p.theta / p.pi) * Point(p.sinpr, p.cospr)
 }
 
 // Spiral
-pub fn v9(p: &PreProc) -> Point {
+pub fn v9(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     p.r_inv * Point(p.cost + p.sinr, p.sint - p.cosr)
 }
 
 // Hyperbolic
-pub fn v10(p: &PreProc) -> Point {
+pub fn v10(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     Point(p.sint * p.r_inv, p.r * p.cost)
 }
 
 // Diamond
-pub fn v11(p: &PreProc) -> Point {
+pub fn v11(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     Point(p.sint * p.cosr, p.cost * p.sinr)
 }
 
 // Ex
-pub fn v12(p: &PreProc) -> Point {
+pub fn v12(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     let p03 = p.sintr_sum * p.sintr_sum * p.sintr_sum;
     let p13 = p.costr_sum * p.costr_sum * p.costr_sum;
     p.r * Point(p03 + p13, p03 - p13)
 }
 
 // Julia
-pub fn v13(p: &PreProc) -> Point {
+pub fn v13(p: &PreProc, rng: &mut dyn Rng) -> Point {
     let sqrtr = p.r.sqrt();
-    // let omega = 0.0; // randomly 0 or pi
-    let omega = (js_sys::Math::random() - 0.5).signum() as f32; // randomly 0 or pi
-    let sinto = (p.theta / 2.0 + omega).sin();
-    let costo = (p.theta / 2.0 + omega).cos();
+    // Randomly picks one of the two square roots
+    let omega = if rng.next_f32() < 0.5 { 0.0 } else { p.pi };
+    let sinto = libm::sinf(p.phi / 2.0 + omega);
+    let costo = libm::cosf(p.phi / 2.0 + omega);
     sqrtr * Point(costo, sinto)
 }
 
 // Bent
-pub fn v14(p: &PreProc) -> Point {
+pub fn v14(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     let x = if p.x >= 0.0 { p.x } else { 2.0 * p.x };
-    let y = if p.y >= 0.0 { p.y } else { p.x / 2.0 };
+    let y = if p.y >= 0.0 { p.y } else { p.y / 2.0 };
     Point(x, y)
 }
 
 // Waves
-pub fn v15(p: &PreProc) -> Point {
-    let sinyc2 = (p.y / (p.coeffs.2 * p.coeffs.2)).sin();
-    let sinxf2 = (p.x / (p.coeffs.5 * p.coeffs.5)).sin();
+pub fn v15(p: &PreProc, _rng: &mut dyn Rng) -> Point {
+    let sinyc2 = libm::sinf(p.y / (p.coeffs.2 * p.coeffs.2));
+    let sinxf2 = libm::sinf(p.x / (p.coeffs.5 * p.coeffs.5));
     Point(p.x + p.coeffs.1 * sinyc2, p.y + p.coeffs.4 * sinxf2)
 }
 
 // Fisheye
-pub fn v16(p: &PreProc) -> Point {
+pub fn v16(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     (2.0 / (p.r + 1.0)) * Point(p.y, p.x)
 }
 
 // Popcorn
-#[allow(unused)]
-pub fn v17(p: &PreProc) -> Point {
-    let sintan3y = ((3.0 * p.y).tan()).sin();
-    let sintan3x = ((3.0 * p.x).tan()).sin();
+pub fn v17(p: &PreProc, _rng: &mut dyn Rng) -> Point {
+    let sintan3y = libm::sinf(libm::tanf(3.0 * p.y));
+    let sintan3x = libm::sinf(libm::tanf(3.0 * p.x));
     Point(p.x + p.coeffs.2 * sintan3y, p.y + p.coeffs.5 * sintan3x)
 }
 
-// // Exponential
-// pub fn v18(p: &PreProc) -> Point {
-//     let expx1 = js_sys::Math::exp(p.x as f64 - 1.0) as f32;
-//      expx1 * Point(p.cospy, p.sinpy)
-// }
+// Exponential
+pub fn v18(p: &PreProc, _rng: &mut dyn Rng) -> Point {
+    let expx1 = libm::expf(p.x - 1.0);
+    expx1 * Point(p.cospy, p.sinpy)
+}
 
 // Power
-pub fn v19(p: &PreProc) -> Point {
-    let rsint = p.r.powf(p.sint);
+pub fn v19(p: &PreProc, _rng: &mut dyn Rng) -> Point {
+    let rsint = libm::powf(p.r, p.sint);
     rsint * Point(p.cost, p.sint)
 }
 
-// // Cosine
-// pub fn v20(p: &PreProc) -> Point {
-//     let sinx = (p.pi * p.x).sin();
-//     let cosx = (p.pi * p.x).cos();
-//     let sinhy = p.y.sinh();
-//     let coshy = p.y.cosh();
-//     Point(cosx * coshy, -sinx * sinhy)
-// }
+// Cosine
+pub fn v20(p: &PreProc, _rng: &mut dyn Rng) -> Point {
+    let sinx = libm::sinf(p.pi * p.x);
+    let cosx = libm::cosf(p.pi * p.x);
+    let sinhy = libm::sinhf(p.y);
+    let coshy = libm::coshf(p.y);
+    Point(cosx * coshy, -sinx * sinhy)
+}
 
 // Rings
-#[allow(unused)]
-pub fn v21(p: &PreProc) -> Point {
+pub fn v21(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     let c2 = p.coeffs.2 * p.coeffs.2;
     let modulo = (p.r + c2).rem_euclid(2.0 * c2);
     let factor = modulo - c2 + p.r * (1.0 - c2);
@@ -277,84 +905,237 @@ pub fn v21(p: &PreProc) -> Point {
 }
 
 // Fan
-#[allow(unused)]
-pub fn v22(p: &PreProc) -> Point {
+pub fn v22(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     let t = p.pi * p.coeffs.2 * p.coeffs.2;
-    let modulo = (p.theta + p.coeffs.5).rem_euclid(t);
+    // Remainder of flam3 (fmod), negative for negative angles
+    let modulo = (p.theta + p.coeffs.5) % t;
     match modulo {
         m if m > t / 2.0 => {
-            let sin_minus = (p.theta - t / 2.0).sin();
-            let cos_minus = (p.theta - t / 2.0).cos();
+            let sin_minus = libm::sinf(p.theta - t / 2.0);
+            let cos_minus = libm::cosf(p.theta - t / 2.0);
             p.r * Point(cos_minus, sin_minus)
         }
         _ => {
-            let sin_plus = (p.theta + t / 2.0).sin();
-            let cos_plus = (p.theta + t / 2.0).cos();
+            let sin_plus = libm::sinf(p.theta + t / 2.0);
+            let cos_plus = libm::cosf(p.theta + t / 2.0);
             p.r * Point(cos_plus, sin_plus)
         }
     }
 }
 
 // Blob
-pub fn v23(p: &PreProc) -> Point {
-    let p1 = p.params.blob.low;
-    let p2 = (p.params.blob.high - p.params.blob.low) / 2.0;
-    let p3 = (p.params.blob.waves * p.theta).sin();
+fn v23(v: &Blob, p: &PreProc, _rng: &mut dyn Rng) -> Point {
+    let p1 = v.low;
+    let p2 = (v.high - v.low) / 2.0;
+    let p3 = libm::sinf(v.waves * p.theta);
     let factor = p.r * (p1 + p2 * (p3 + 1.0));
-    factor * Point(p.cost, p.sint)
+    factor * Point(p.sint, p.cost)
 }
 
 // PDJ
-pub fn v24(p: &PreProc) -> Point {
+fn v24(v: &Pdj, p: &PreProc, _rng: &mut dyn Rng) -> Point {
     Point(
-        (p.params.pdj.a * p.y).sin() - (p.params.pdj.b * p.x).cos(),
-        (p.params.pdj.c * p.x).sin() - (p.params.pdj.d * p.y).cos(),
+        libm::sinf(v.a * p.y) - libm::cosf(v.b * p.x),
+        libm::sinf(v.c * p.x) - libm::cosf(v.d * p.y),
     )
 }
 
 // Fan2
-#[allow(unused)]
-pub fn v25(p: &PreProc) -> Point {
-    let p1 = 0.5 * p.pi * p.params.fan.x * p.params.fan.x;
-    let p2 = p.params.fan.y;
-    let t = p.theta + p2 - 2.0 * p1 * (p.theta * p2 / p1).trunc();
+fn v25(v: &Fan2, p: &PreProc, _rng: &mut dyn Rng) -> Point {
+    let p1 = 0.5 * p.pi * v.x * v.x;
+    let p2 = v.y;
+    let t = p.theta + p2 - 2.0 * p1 * ((p.theta + p2) / (2.0 * p1)).trunc();
     if t > p1 {
-        p.r * Point((p.theta - p1).sin(), (p.theta - p1).cos())
+        p.r * Point(libm::sinf(p.theta - p1), libm::cosf(p.theta - p1))
     } else {
-        p.r * Point((p.theta + p1).sin(), (p.theta + p1).cos())
+        p.r * Point(libm::sinf(p.theta + p1), libm::cosf(p.theta + p1))
     }
 }
 
-// // Rings2
-// pub fn v26(p: &PreProc) -> Point {
-//     Point(0.0, 0.0)
-// }
+// Rings2
+fn v26(v: &Rings2, p: &PreProc, _rng: &mut dyn Rng) -> Point {
+    let dx = v.val * v.val + EPS;
+    let r = p.r - 2.0 * dx * ((p.r + dx) / (2.0 * dx)).trunc() + p.r * (1.0 - dx);
+    r * Point(p.sint, p.cost)
+}
 
 // Eyefish
-pub fn v27(p: &PreProc) -> Point {
+pub fn v27(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     (2.0 / (p.r + 1.0)) * Point(p.x, p.y)
 }
 
 // Bubble
-pub fn v28(p: &PreProc) -> Point {
+pub fn v28(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     (4.0 / (p.r2 + 4.0)) * Point(p.x, p.y)
 }
 
 // Cylinder
-pub fn v29(p: &PreProc) -> Point {
+pub fn v29(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     Point(p.sinx, p.y)
 }
 
+// Perspective
+fn v30(v: &Perspective, p: &PreProc, _rng: &mut dyn Rng) -> Point {
+    let angle = v.angle * p.pi / 2.0;
+    let t = 1.0 / (v.dist - p.y * libm::sinf(angle));
+    t * Point(v.dist * p.x, v.dist * libm::cosf(angle) * p.y)
+}
+
+// Noise
+pub fn v31(p: &PreProc, rng: &mut dyn Rng) -> Point {
+    let angle = 2.0 * p.pi * rng.next_f32();
+    let r = rng.next_f32();
+    r * Point(p.x * libm::cosf(angle), p.y * libm::sinf(angle))
+}
+
+// Julian
+fn v32(v: &Julian, p: &PreProc, rng: &mut dyn Rng) -> Point {
+    let root = (v.power.abs() * rng.next_f32()).trunc();
+    let angle = (p.phi + 2.0 * p.pi * root) / v.power;
+    let r = libm::powf(p.r2, v.dist / v.power / 2.0);
+    r * Point(libm::cosf(angle), libm::sinf(angle))
+}
+
+// JuliaScope
+fn v33(v: &JuliaScope, p: &PreProc, rng: &mut dyn Rng) -> Point {
+    let root = (v.power.abs() * rng.next_f32()).trunc();
+    // Odd roots are mirrored
+    let angle = if root % 2.0 == 0.0 {
+        (2.0 * p.pi * root + p.phi) / v.power
+    } else {
+        (2.0 * p.pi * root - p.phi) / v.power
+    };
+    let r = libm::powf(p.r2, v.dist / v.power / 2.0);
+    r * Point(libm::cosf(angle), libm::sinf(angle))
+}
+
+// Blur
+pub fn v34(p: &PreProc, rng: &mut dyn Rng) -> Point {
+    let angle = 2.0 * p.pi * rng.next_f32();
+    let r = rng.next_f32();
+    r * Point(libm::cosf(angle), libm::sinf(angle))
+}
+
+/// Sum of four uniform random numbers minus 2, a cheap approximation of a gaussian in [-2, 2]
+fn gaussian(rng: &mut dyn Rng) -> f32 {
+    rng.next_f32() + rng.next_f32() + rng.next_f32() + rng.next_f32() - 2.0
+}
+
+// Gaussian blur
+pub fn v35(p: &PreProc, rng: &mut dyn Rng) -> Point {
+    let angle = 2.0 * p.pi * rng.next_f32();
+    let r = gaussian(rng);
+    r * Point(libm::cosf(angle), libm::sinf(angle))
+}
+
+// Radial blur
+fn v36(v: &RadialBlur, p: &PreProc, weight: f32, rng: &mut dyn Rng) -> Point {
+    let spin = libm::sinf(v.angle * p.pi / 2.0);
+    let zoom = libm::cosf(v.angle * p.pi / 2.0);
+    let blur = weight * gaussian(rng);
+    let angle = p.phi + spin * blur;
+    let rz = zoom * blur - 1.0;
+    p.r * Point(libm::cosf(angle), libm::sinf(angle)) + rz * Point(p.x, p.y)
+}
+
+// Pie
+fn v37(v: &Pie, p: &PreProc, rng: &mut dyn Rng) -> Point {
+    let slice = (rng.next_f32() * v.slices + 0.5).trunc();
+    let angle = v.rotation + 2.0 * p.pi * (slice + rng.next_f32() * v.thickness) / v.slices;
+    let r = rng.next_f32();
+    r * Point(libm::cosf(angle), libm::sinf(angle))
+}
+
+// Ngon
+fn v38(v: &Ngon, p: &PreProc, _rng: &mut dyn Rng) -> Point {
+    let r_factor = libm::powf(p.r2, v.power / 2.0);
+    let b = 2.0 * p.pi / v.sides;
+    let mut phi = p.phi - b * (p.phi / b).floor();
+    if phi > b / 2.0 {
+        phi -= b;
+    }
+    let amp = (v.corners * (1.0 / (libm::cosf(phi) + EPS) - 1.0) + v.circle) / (r_factor + EPS);
+    amp * Point(p.x, p.y)
+}
+
 // Curl
-pub fn v39(p: &PreProc) -> Point {
-    let p1 = p.params.curl.c1;
-    let p2 = p.params.curl.c2;
+fn v39(v: &Curl, p: &PreProc, _rng: &mut dyn Rng) -> Point {
+    let p1 = v.c1;
+    let p2 = v.c2;
     let t1 = 1.0 + p1 * p.x + p2 * (p.x * p.x - p.y * p.y);
     let t2 = p1 * p.y + 2.0 * p2 * p.x * p.y;
     1.0 / (t1 * t1 + t2 * t2) * Point(p.x * t1 + p.y * t2, p.y * t1 - p.x * t2)
 }
 
+// Rectangles
+fn v40(v: &Rectangles, p: &PreProc, _rng: &mut dyn Rng) -> Point {
+    let fold = |t: f32, size: f32| {
+        if size == 0.0 {
+            t
+        } else {
+            (2.0 * (t / size).floor() + 1.0) * size - t
+        }
+    };
+    Point(fold(p.x, v.x), fold(p.y, v.y))
+}
+
+// Arch
+pub fn v41(p: &PreProc, weight: f32, rng: &mut dyn Rng) -> Point {
+    let angle = rng.next_f32() * weight * p.pi;
+    let sinr = libm::sinf(angle);
+    weight * Point(sinr, sinr * sinr / libm::cosf(angle))
+}
+
 // Tangent
-pub fn v42(p: &PreProc) -> Point {
+pub fn v42(p: &PreProc, _rng: &mut dyn Rng) -> Point {
     Point(p.sinx / p.cosy, p.tany)
 }
+
+// Square
+pub fn v43(_p: &PreProc, rng: &mut dyn Rng) -> Point {
+    Point(rng.next_f32() - 0.5, rng.next_f32() - 0.5)
+}
+
+// Rays
+pub fn v44(p: &PreProc, weight: f32, rng: &mut dyn Rng) -> Point {
+    let angle = weight * rng.next_f32() * p.pi;
+    let r = weight / (p.r2 + EPS);
+    let tanr = weight * libm::tanf(angle) * r;
+    tanr * Point(libm::cosf(p.x), libm::sinf(p.y))
+}
+
+// Blade
+pub fn v45(p: &PreProc, weight: f32, rng: &mut dyn Rng) -> Point {
+    let r = rng.next_f32() * weight * p.r;
+    let sinr = libm::sinf(r);
+    let cosr = libm::cosf(r);
+    weight * p.x * Point(cosr + sinr, cosr - sinr)
+}
+
+// Secant2
+pub fn v46(p: &PreProc, weight: f32, _rng: &mut dyn Rng) -> Point {
+    let cosr = libm::cosf(weight * p.r);
+    let y = if cosr < 0.0 {
+        1.0 / cosr + 1.0
+    } else {
+        1.0 / cosr - 1.0
+    };
+    weight * Point(p.x, y)
+}
+
+// Twintrian
+pub fn v47(p: &PreProc, weight: f32, rng: &mut dyn Rng) -> Point {
+    let r = rng.next_f32() * weight * p.r;
+    let sinr = libm::sinf(r);
+    let mut diff = libm::log10f(sinr * sinr) + libm::cosf(r);
+    if !diff.is_finite() {
+        diff = -30.0;
+    }
+    weight * p.x * Point(diff, diff - sinr * p.pi)
+}
+
+// Cross
+pub fn v48(p: &PreProc, _rng: &mut dyn Rng) -> Point {
+    let s = p.x * p.x - p.y * p.y;
+    (1.0 / (s * s + EPS)).sqrt() * Point(p.x, p.y)
+}
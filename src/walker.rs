@@ -0,0 +1,151 @@
+//! Walkers of the chaos game
+//!
+//! A render is shared between `WALKERS` independent walkers, each drawing its random choices
+//! from its own stream of the seed of the render. A walker keeps its position between calls to
+//! `run`, so a picture can be refined by running more iterations.
+
+use super::genome::{Color, Genome, Xform};
+use super::histogram::Histogram;
+use super::palette;
+use super::point::Point;
+use super::rng::{Pcg32, Rng};
+use super::WALKERS;
+
+/// Returns the number of iterations of `walker` when `iterations` are shared between all the
+/// walkers
+pub fn share(iterations: u64, walker: u64) -> u64 {
+    iterations / WALKERS + (walker < iterations % WALKERS) as u64
+}
+
+/// Returns the number of iterations of `walker` when `iterations` more are shared between all
+/// the walkers after `done` iterations. Sharing iterations in several steps gives each walker as
+/// many iterations as sharing them at once.
+pub fn share_after(done: u64, iterations: u64, walker: u64) -> u64 {
+    share(done + iterations, walker) - share(done, walker)
+}
+
+/// State of a walker of the chaos game
+#[derive(Debug, Clone)]
+pub struct Walker {
+    rng: Pcg32,
+    coord: Point,
+    /// Color index of the point, in [0, 1]
+    color: f32,
+    /// Number of iterations skipped whenever the walker is placed on a new point
+    fuse: u32,
+    /// Number of times the point became NaN or infinite since they were last recorded in a
+    /// histogram
+    bad_values: u64,
+}
+
+impl Walker {
+    /// Creates the walker number `index` of the render of `seed`, and runs its first `fuse`
+    /// iterations
+    pub fn new(genome: &Genome, seed: u64, index: u64, fuse: u32) -> Walker {
+        let mut rng = Pcg32::with_stream(seed, index);
+        let coord = random_point(&mut rng);
+        let color = rng.next_f32();
+        let mut walker = Walker {
+            rng,
+            coord,
+            color,
+            fuse,
+            bad_values: 0,
+        };
+        walker.run_fuse(genome);
+        walker
+    }
+
+    /// Skips the first `fuse` iterations from the point of the walker
+    ///
+    /// After a few iterations (20 by default) any random point has fallen into the actual shape
+    /// of the attractor and we can start recording data for the picture
+    fn run_fuse(&mut self, genome: &Genome) {
+        let threshold = genome.thresholds();
+        for _ in 0..self.fuse {
+            if let Some(idx) = pick(&threshold, self.rng.next_f32()) {
+                if !self.apply(&genome.xforms[idx]) {
+                    self.coord = random_point(&mut self.rng);
+                }
+            }
+        }
+    }
+
+    /// Applies `xform` to the point of the walker. Returns false, and counts a bad value, if
+    /// the point is not finite anymore (such as after a division by zero at the origin).
+    fn apply(&mut self, xform: &Xform) -> bool {
+        self.coord = xform.apply(self.coord, &mut self.rng);
+        self.color = xform.blend_color(self.color);
+        let finite = self.coord.is_finite();
+        if !finite {
+            self.bad_values += 1;
+        }
+        finite
+    }
+
+    /// Runs `iterations` steps of the chaos game, recording in `histogram` the points that
+    /// `index` maps to a cell, with the color of `palette` at their color index
+    pub fn run<F>(
+        &mut self,
+        genome: &Genome,
+        palette: &[Color],
+        iterations: u64,
+        index: F,
+        histogram: &mut Histogram,
+    ) where
+        F: Fn(&Point) -> Option<usize>,
+    {
+        let threshold = genome.thresholds();
+
+        // Closelly following the algorithm in Draves (2003)
+        //
+        // 1. Pics a random function. Each function has a probability threshold
+        // 2. Applies an affine transformation
+        // 3. Applies a function (a weighted set of variations)
+        // 4. Applies an affine transformation
+        // 5. Applies a final common function to a copy of the point, which is drawn while the
+        //    walker goes on from the point itself, as in flam3
+        // 6. The resulting point is stored as a counter and the color of the palette at its color
+        //    index, which moves towards the color of each xform applied
+        //
+        // As in flam3, a point that is not finite anymore would stay so and is not drawn: the
+        // walker starts again from a random point, and skips the fuse iterations again
+        for _ in 0..iterations {
+            if let Some(idx) = pick(&threshold, self.rng.next_f32()) {
+                if !self.apply(&genome.xforms[idx]) {
+                    self.coord = random_point(&mut self.rng);
+                    self.run_fuse(genome);
+                    continue;
+                }
+            }
+
+            let (coord, color) = match &genome.final_xform {
+                Some(final_xform) => {
+                    let coord = final_xform.apply(self.coord, &mut self.rng);
+                    if !coord.is_finite() {
+                        // Only the drawn copy is lost, the walker's own point is still fine
+                        self.bad_values += 1;
+                        continue;
+                    }
+                    (coord, final_xform.blend_color(self.color))
+                }
+                None => (self.coord, self.color),
+            };
+
+            if let Some(idx) = index(&coord) {
+                histogram.add(idx, palette::color_at(palette, color));
+            }
+        }
+        histogram.bad_values += std::mem::take(&mut self.bad_values);
+    }
+}
+
+/// Returns the xform picked by `val`, in [0, 1): the first one whose threshold is greater
+fn pick(threshold: &[f32], val: f32) -> Option<usize> {
+    threshold.iter().position(|&t| val < t)
+}
+
+/// Returns a random point of the [-1, 1] square
+fn random_point(rng: &mut dyn Rng) -> Point {
+    Point(rng.range(-1.0, 1.0), rng.range(-1.0, 1.0))
+}
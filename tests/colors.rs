@@ -0,0 +1,77 @@
+//! Checks that the color index of the points moves towards the color of the xforms by their
+//! color speed
+
+use fractal_flames::{create_variation, Color, Genome, Pcg32, Picture, RenderOptions, Xform};
+
+/// Xform of color index `color` sending every point to the origin
+fn xform(color: f32, color_speed: f32) -> Xform {
+    Xform {
+        weight: 1.0,
+        coeffs_pre: (0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+        coeffs_post: (1.0, 0.0, 0.0, 0.0, 1.0, 0.0),
+        variations: vec![(create_variation("linear").unwrap(), 1.0)],
+        color,
+        color_speed,
+    }
+}
+
+/// Palette going from blue at index 0 to red at index 255
+fn palette() -> Vec<Color> {
+    (0..256)
+        .map(|idx| (idx as f32 / 255.0, 0.0, 1.0 - idx as f32 / 255.0))
+        .collect()
+}
+
+/// Returns the share of red in the colors of the cells that are drawn
+fn red_shares(genome: &Genome) -> Vec<f32> {
+    // Dark enough that no channel is clamped
+    let options = RenderOptions::builder()
+        .size(32, 32)
+        .iterations(10_000)
+        .brightness(0.05)
+        .build()
+        .unwrap();
+    let mut picture = Picture::with_options(options).unwrap();
+    picture.render(genome, &mut Pcg32::new(4));
+    let shares: Vec<f32> = picture
+        .colors()
+        .iter()
+        .filter(|&&(red, _, blue)| red + blue > 0.0)
+        .map(|&(red, _, blue)| red / (red + blue))
+        .collect();
+    assert!(!shares.is_empty());
+    shares
+}
+
+fn flame(xform: Xform) -> Genome {
+    Genome {
+        xforms: vec![xform],
+        final_xform: None,
+        palette: palette(),
+    }
+}
+
+#[test]
+fn color_speeds_blend_color_indices() {
+    for (speed, expected) in [(0.0, 0.2), (0.5, 0.5), (1.0, 0.8)] {
+        assert_eq!(xform(0.8, speed).blend_color(0.2), expected);
+    }
+}
+
+#[test]
+fn points_take_the_color_of_an_xform_of_speed_one() {
+    // Color index 0.75 is the entry 192 of the palette
+    let expected = palette()[192].0;
+    for share in red_shares(&flame(xform(0.75, 1.0))) {
+        assert!((share - expected).abs() < 1e-5, "{} {}", share, expected);
+    }
+}
+
+#[test]
+fn points_keep_their_color_with_an_xform_of_speed_zero() {
+    // The walkers keep the random color index they start with, whatever the color of the xform
+    let shares = red_shares(&flame(xform(0.75, 0.0)));
+    assert_eq!(shares, red_shares(&flame(xform(0.25, 0.0))));
+    let expected = palette()[192].0;
+    assert!(shares.iter().all(|share| (share - expected).abs() > 0.01));
+}
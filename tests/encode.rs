@@ -0,0 +1,131 @@
+//! Checks the headers and samples of the pictures written by the encoders, without decoding them
+
+use fractal_flames::encode::{self, BitDepth};
+use fractal_flames::{Picture, RenderOptions};
+
+const WIDTH: u32 = 12;
+const HEIGHT: u32 = 8;
+
+fn picture() -> Picture {
+    let options = RenderOptions::builder()
+        .size(WIDTH, HEIGHT)
+        .iterations(5_000)
+        .build()
+        .unwrap();
+    let mut picture = Picture::with_options(options).unwrap();
+    picture.paint_with_seed(5);
+    assert!(picture
+        .colors()
+        .iter()
+        .any(|&color| color != (0.0, 0.0, 0.0)));
+    picture
+}
+
+/// Returns the samples of `data` after a header of `header` bytes, as numbers
+fn samples(data: &[u8], header: usize, depth: BitDepth) -> Vec<u16> {
+    match depth {
+        BitDepth::Eight => data[header..].iter().map(|&byte| byte as u16).collect(),
+        BitDepth::Sixteen => data[header..]
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect(),
+    }
+}
+
+/// Returns the samples of the RGB channels of the picture
+fn rgb_samples(picture: &Picture, depth: BitDepth) -> Vec<u16> {
+    let max = match depth {
+        BitDepth::Eight => 255.0,
+        BitDepth::Sixteen => 65535.0,
+    };
+    picture
+        .colors()
+        .iter()
+        .flat_map(|&(r, g, b)| [r, g, b])
+        .map(|channel| (channel.clamp(0.0, 1.0) * max).round() as u16)
+        .collect()
+}
+
+#[test]
+fn ppm_header_and_samples() {
+    let picture = picture();
+    for (depth, maxval) in [(BitDepth::Eight, 255), (BitDepth::Sixteen, 65535)] {
+        let data = encode::to_ppm(&picture, depth);
+        let header = format!("P6\n{} {}\n{}\n", WIDTH, HEIGHT, maxval);
+        assert!(data.starts_with(header.as_bytes()), "{:?}", depth);
+        assert_eq!(
+            samples(&data, header.len(), depth),
+            rgb_samples(&picture, depth)
+        );
+    }
+}
+
+#[test]
+fn pam_header_and_samples() {
+    let picture = picture();
+    for (depth, maxval) in [(BitDepth::Eight, 255), (BitDepth::Sixteen, 65535)] {
+        let data = encode::to_pam(&picture, depth, false);
+        let header = format!(
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 3\nMAXVAL {}\nTUPLTYPE RGB\nENDHDR\n",
+            WIDTH, HEIGHT, maxval
+        );
+        assert!(data.starts_with(header.as_bytes()), "{:?}", depth);
+        assert_eq!(
+            samples(&data, header.len(), depth),
+            rgb_samples(&picture, depth)
+        );
+    }
+}
+
+#[test]
+fn pam_alpha_header_and_samples() {
+    let picture = picture();
+    let mut alpha = Vec::new();
+    for (depth, maxval) in [(BitDepth::Sixteen, 65535), (BitDepth::Eight, 255)] {
+        let data = encode::to_pam(&picture, depth, true);
+        let header = format!(
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL {}\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+            WIDTH, HEIGHT, maxval
+        );
+        assert!(data.starts_with(header.as_bytes()), "{:?}", depth);
+        let samples = samples(&data, header.len(), depth);
+        assert_eq!(samples.len(), (WIDTH * HEIGHT * 4) as usize);
+        let rgb: Vec<u16> = samples
+            .chunks_exact(4)
+            .flat_map(|pixel| pixel[..3].to_vec())
+            .collect();
+        assert_eq!(rgb, rgb_samples(&picture, depth));
+        let pixel_alpha = samples.chunks_exact(4).map(|pixel| pixel[3]);
+        match depth {
+            BitDepth::Sixteen => alpha = pixel_alpha.collect(),
+            // Same alpha at both depths
+            BitDepth::Eight => {
+                for (alpha8, &alpha16) in pixel_alpha.zip(&alpha) {
+                    let expected = (alpha16 as f32 * 255.0 / 65535.0).round() as u16;
+                    assert!(alpha8.abs_diff(expected) <= 1, "{} {}", alpha8, alpha16);
+                }
+            }
+        }
+    }
+    assert!(alpha.iter().any(|&alpha| alpha > 0));
+}
+
+#[test]
+fn png_header() {
+    let picture = picture();
+    let cases = [
+        (BitDepth::Eight, false, 8, 2),
+        (BitDepth::Eight, true, 8, 6),
+        (BitDepth::Sixteen, false, 16, 2),
+        (BitDepth::Sixteen, true, 16, 6),
+    ];
+    for (depth, alpha, bit_depth, color_type) in cases {
+        let data = encode::to_png(&picture, depth, alpha);
+        assert_eq!(&data[..8], b"\x89PNG\r\n\x1a\n");
+        // The IHDR chunk comes first: its length, type, width, height, bit depth and color type
+        assert_eq!(&data[8..16], b"\0\0\0\x0dIHDR");
+        assert_eq!(&data[16..20], &WIDTH.to_be_bytes());
+        assert_eq!(&data[20..24], &HEIGHT.to_be_bytes());
+        assert_eq!((data[24], data[25]), (bit_depth, color_type), "{:?}", depth);
+    }
+}
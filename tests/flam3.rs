@@ -0,0 +1,188 @@
+//! Checks reading and writing flames in the flam3 format
+
+use fractal_flames::flam3::{self, Error};
+use fractal_flames::{
+    Color, FilterShape, Fit, Genome, GenomeOptions, Pcg32, RenderOptions, Xform, PALETTE_SIZE,
+    VARIATIONS,
+};
+
+/// Document with a single flame whose only xform has the attributes `attributes`
+fn flame(attributes: &str) -> String {
+    format!(
+        r#"<flame><xform weight="0.5" color="0" coefs="1 0 0 1 0 0" {}/></flame>"#,
+        attributes
+    )
+}
+
+#[test]
+fn unknown_attributes_are_unsupported() {
+    for name in ["hemisphere", "julia3D"] {
+        let error = flam3::parse(&flame(&format!("{}=\"1\"", name))).unwrap_err();
+        assert_eq!(
+            error,
+            Error::UnsupportedVariation {
+                name: name.to_string(),
+                xform: Some(0),
+            }
+        );
+    }
+    // Even with a zero weight, an unknown attribute is not silently dropped
+    assert!(flam3::parse(&flame("hemisphere=\"0\"")).is_err());
+}
+
+#[test]
+fn known_attributes_are_accepted() {
+    let genomes = flam3::parse(&flame(concat!(
+        r#"linear="1" opacity="1" var_color="1" chaos="1 1" plotmode="on" name="a" "#,
+        r#"animate="1" julian="0" julian_power="2" bipolar="0" bipolar_shift="1""#,
+    )))
+    .unwrap();
+    let xform = &genomes[0].xforms[0];
+    assert_eq!(xform.variations.len(), 1);
+    assert_eq!(xform.variations[0].0.name(), "linear");
+}
+
+#[test]
+fn unsupported_settings_are_rejected() {
+    let settings = [
+        ("opacity", "0"),
+        ("var_color", "0.5"),
+        ("chaos", "1 0"),
+        ("plotmode", "off"),
+    ];
+    for (name, value) in settings {
+        let error = flam3::parse(&flame(&format!("linear=\"1\" {}=\"{}\"", name, value)));
+        assert_eq!(
+            error.unwrap_err(),
+            Error::UnsupportedSetting {
+                name: name.to_string(),
+                value: value.to_string(),
+                xform: Some(0),
+            }
+        );
+    }
+    let final_xform = r#"<flame>
+        <xform coefs="1 0 0 1 0 0" linear="1"/>
+        <finalxform coefs="1 0 0 1 0 0" linear="1" opacity="0.5"/>
+    </flame>"#;
+    assert_eq!(
+        flam3::parse(final_xform).unwrap_err(),
+        Error::UnsupportedSetting {
+            name: "opacity".to_string(),
+            value: "0.5".to_string(),
+            xform: None,
+        }
+    );
+    assert!(matches!(
+        flam3::parse(&flame(r#"linear="1" opacity="half""#)),
+        Err(Error::InvalidAttribute { .. })
+    ));
+}
+
+#[test]
+fn variations_round_trip() {
+    // One xform with each variation of the registry, with random parameters
+    let mut rng = Pcg32::new(3);
+    let xforms: Vec<Xform> = (0..VARIATIONS.len())
+        .map(|idx| Xform::random(&mut rng, &[idx]))
+        .collect();
+    let genome = Genome {
+        xforms,
+        final_xform: None,
+        palette: vec![(1.0, 1.0, 1.0); PALETTE_SIZE],
+    };
+    let parsed = flam3::parse(&flam3::write(std::slice::from_ref(&genome))).unwrap();
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(parsed[0].xforms, genome.xforms);
+}
+
+#[test]
+fn flames_round_trip_with_options() {
+    let mut rng = Pcg32::new(11);
+    let mut genome = Genome::random(&mut rng, &GenomeOptions::default());
+    let mut final_xform = Xform::random(&mut rng, &[0, 3]);
+    final_xform.weight = 0.25;
+    genome.final_xform = Some(final_xform);
+    let options = RenderOptions::builder()
+        .size(300, 200)
+        .fit(Fit::LongerEdge)
+        .center(0.25, -0.5)
+        .scale(1.7)
+        .zoom(0.3)
+        .rotate(12.5)
+        .supersample(3)
+        .filter_shape(FilterShape::Mitchell)
+        .filter_radius(0.8)
+        .iterations(123_457)
+        .num_xforms(4)
+        .variations(&["swirl", "julia"])
+        .gamma(2.2)
+        .gamma_threshold(0.02)
+        .brightness(3.5)
+        .contrast(1.2)
+        .vibrancy(0.7)
+        .estimator_radius(7.0)
+        .estimator_minimum(0.5)
+        .estimator_curve(0.6)
+        .fuse(30)
+        .palette(Some(2))
+        .hue(0.125)
+        .reverse_palette(true)
+        .build()
+        .unwrap();
+
+    let xml = flam3::write_with_options(&[(genome.clone(), options.clone())]);
+    let parsed = flam3::parse_with_options(&xml).unwrap();
+    assert_eq!(parsed.len(), 1);
+    let (parsed_genome, parsed_options) = &parsed[0];
+    assert_eq!(parsed_options, &options);
+    assert_eq!(parsed_genome.xforms, genome.xforms);
+    assert_eq!(parsed_genome.final_xform, genome.final_xform);
+    // Palettes are written with 8 bits per channel
+    let quantize = |palette: &[Color]| -> Vec<[u8; 3]> {
+        let byte = |channel: f32| (channel * 255.0).round() as u8;
+        palette
+            .iter()
+            .map(|&(r, g, b)| [byte(r), byte(g), byte(b)])
+            .collect()
+    };
+    assert_eq!(quantize(&parsed_genome.palette), quantize(&genome.palette));
+}
+
+#[test]
+fn defaults_round_trip() {
+    let genome = Genome::random(&mut Pcg32::new(5), &GenomeOptions::default());
+    let xml = flam3::write(std::slice::from_ref(&genome));
+    let parsed = flam3::parse_with_options(&xml).unwrap();
+    assert_eq!(parsed[0].1, RenderOptions::default());
+}
+
+#[test]
+fn flam3_palette_numbers_are_not_built_in_palettes() {
+    // The colors of the flame are its palette, whatever its number in the flam3 collection
+    let xml = concat!(
+        r#"<flame palette="3"><xform weight="1" color="0" linear="1" coefs="1 0 0 1 0 0"/>"#,
+        r#"<color index="0" rgb="255 0 0"/></flame>"#,
+    );
+    let (genome, options) = &flam3::parse_with_options(xml).unwrap()[0];
+    assert_eq!(options.palette, None);
+    assert_eq!(genome.palette[0], (1.0, 0.0, 0.0));
+
+    // Without colors, the palette of the flam3 collection is not available
+    let xml = concat!(
+        r#"<flame palette="42">"#,
+        r#"<xform weight="1" color="0" linear="1" coefs="1 0 0 1 0 0"/></flame>"#,
+    );
+    assert_eq!(
+        flam3::parse_with_options(xml).unwrap_err(),
+        Error::UnsupportedPalette(42)
+    );
+
+    // The built-in palettes of this crate are written apart from the numbers of flam3
+    let options = RenderOptions::builder().palette(Some(3)).build().unwrap();
+    let genome = Genome::random(&mut Pcg32::new(5), &GenomeOptions::default());
+    let xml = flam3::write_with_options(&[(genome, options.clone())]);
+    assert!(xml.contains(r#" builtin_palette="3""#));
+    assert!(!xml.contains(r#" palette="3""#));
+    assert_eq!(flam3::parse_with_options(&xml).unwrap()[0].1, options);
+}
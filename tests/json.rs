@@ -0,0 +1,107 @@
+//! Checks the versioned JSON documents and the upgrade of older versions
+#![cfg(feature = "serde")]
+
+use fractal_flames::json::{self, Error, VERSION};
+use fractal_flames::{Fit, Genome, GenomeOptions, Pcg32, RenderOptions, PALETTE_SIZE};
+
+#[test]
+fn documents_round_trip() {
+    let genome = Genome::random(&mut Pcg32::new(8), &GenomeOptions::default());
+    let options = RenderOptions::builder()
+        .size(320, 200)
+        .fit(Fit::LongerEdge)
+        .gamma(3.0)
+        .variations(&["julia", "pdj"])
+        .build()
+        .unwrap();
+    let document = json::to_json(&genome, &options);
+    assert!(document.starts_with(&format!("{{\"version\":{}", VERSION)));
+    assert!(document.contains("\"fit\":\"longeredge\""));
+    assert_eq!(json::from_json(&document).unwrap(), (genome, options));
+}
+
+#[test]
+fn version_1_documents_get_one_palette_band_per_xform() {
+    let document = r#"{
+        "version": 1,
+        "flame": {
+            "xforms": [
+                {"weight": 1.0, "coeffs_pre": [1, 0, 0, 0, 1, 0], "coeffs_post": [1, 0, 0, 0, 1, 0],
+                 "weights": {"linear": 1.0}, "params": {}},
+                {"weight": 1.0, "coeffs_pre": [0.5, 0, 0, 0, 0.5, 0],
+                 "coeffs_post": [1, 0, 0, 0, 1, 0], "weights": {"spherical": 1.0}, "params": {}}
+            ],
+            "final_xform": null,
+            "palette": [[1.0, 0.0, 0.0], [0.0, 0.0, 1.0]]
+        },
+        "settings": {"width": 64, "height": 32}
+    }"#;
+    let (genome, options) = json::from_json(document).unwrap();
+    assert_eq!((options.width, options.height), (64, 32));
+    let colors: Vec<(f32, f32)> = genome
+        .xforms
+        .iter()
+        .map(|xform| (xform.color, xform.color_speed))
+        .collect();
+    assert_eq!(colors, vec![(0.25, 1.0), (0.75, 1.0)]);
+    assert_eq!(genome.palette.len(), PALETTE_SIZE);
+    assert_eq!(genome.palette[0], (1.0, 0.0, 0.0));
+    assert_eq!(genome.palette[PALETTE_SIZE / 2 - 1], (1.0, 0.0, 0.0));
+    assert_eq!(genome.palette[PALETTE_SIZE / 2], (0.0, 0.0, 1.0));
+    assert_eq!(genome.xforms[1].variations[0].0.name(), "spherical");
+}
+
+#[test]
+fn version_2_documents_gather_the_parameters_of_each_variation() {
+    let document = r#"{
+        "version": 2,
+        "flame": {
+            "xforms": [
+                {"weight": 1.0, "coeffs_pre": [1, 0, 0, 0, 1, 0], "coeffs_post": [1, 0, 0, 0, 1, 0],
+                 "weights": {"blob": 0.25, "pdj": 0.75},
+                 "params": {"blob": {"low": 0.2, "high": 0.9, "waves": 3.0},
+                            "pdj": {"a": 1.0, "b": 2.0, "c": 3.0, "d": 4.0}},
+                 "color": 0.5, "color_speed": 0.3}
+            ],
+            "final_xform": null,
+            "palette": [[1.0, 1.0, 1.0]]
+        }
+    }"#;
+    let (genome, options) = json::from_json(document).unwrap();
+    assert_eq!(options, RenderOptions::default());
+    let xform = &genome.xforms[0];
+    assert_eq!((xform.color, xform.color_speed), (0.5, 0.3));
+    let variation = |name: &str| {
+        xform
+            .variations
+            .iter()
+            .find(|(variation, _)| variation.name() == name)
+            .unwrap()
+    };
+    let (blob, blob_weight) = variation("blob");
+    assert_eq!(*blob_weight, 0.25);
+    assert_eq!(blob.param("blob_low"), Some(0.2));
+    assert_eq!(blob.param("blob_high"), Some(0.9));
+    assert_eq!(blob.param("blob_waves"), Some(3.0));
+    let (pdj, pdj_weight) = variation("pdj");
+    assert_eq!(*pdj_weight, 0.75);
+    assert_eq!(pdj.param("pdj_a"), Some(1.0));
+    assert_eq!(pdj.param("pdj_d"), Some(4.0));
+}
+
+#[test]
+fn unknown_versions_are_rejected() {
+    for version in ["0", "4", "\"3\""] {
+        let document = format!(r#"{{"version": {}, "flame": {{}}}}"#, version);
+        assert!(matches!(
+            json::from_json(&document),
+            Err(Error::UnsupportedVersion(_))
+        ));
+    }
+    let document = r#"{"flame": {"xforms": [], "final_xform": null, "palette": []}}"#;
+    assert!(matches!(
+        json::from_json(document),
+        Err(Error::UnsupportedVersion(0))
+    ));
+    assert!(matches!(json::from_json("{"), Err(Error::Json(_))));
+}
@@ -0,0 +1,25 @@
+//! Checks the validation of the render settings
+
+use fractal_flames::{OptionsError, RenderOptions};
+
+#[test]
+fn accumulation_grid_is_capped() {
+    let options = |width, height, supersample| {
+        RenderOptions::builder()
+            .size(width, height)
+            .supersample(supersample)
+            .build()
+    };
+    assert!(options(1920, 1080, 4).is_ok());
+    assert!(options(4096, 4096, 2).is_err());
+    assert_eq!(
+        options(16_384, 16_384, 1),
+        Err(OptionsError::PictureTooLarge {
+            width: 16_384,
+            height: 16_384,
+            supersample: 1,
+        })
+    );
+    // The cell count is computed without overflowing
+    assert!(options(u32::MAX, u32::MAX, u32::MAX).is_err());
+}
@@ -0,0 +1,258 @@
+//! Checks the built-in palettes and the palette files
+
+use fractal_flames::palette;
+use fractal_flames::{Color, PALETTE_SIZE};
+
+#[test]
+fn builtin_palettes_are_selectable_by_number_and_name() {
+    let count = palette::builtin_count();
+    assert!(count > 0);
+    assert_eq!(palette::builtin_names().count(), count as usize);
+    for (number, name) in palette::builtin_names().enumerate() {
+        let number = number as u32;
+        assert_eq!(palette::builtin_index(name), Some(number));
+        assert_eq!(palette::builtin_name(number).as_deref(), Some(name));
+        assert_eq!(palette::builtin(number).unwrap().len(), PALETTE_SIZE);
+    }
+    assert_eq!(palette::builtin(count), None);
+    assert_eq!(palette::builtin_index("no such palette"), None);
+}
+
+#[test]
+fn collections_are_numbered() {
+    let xml = r#"<palettes>
+        <palette number="7" name="red" data="00FF0000 00FF0000"/>
+        <palette number="3" data="0000FF00"/>
+    </palettes>"#;
+    let palettes = palette::parse_flam3_palettes(xml).unwrap();
+    assert_eq!(palettes[0].name, "red");
+    assert_eq!(palettes[0].colors, vec![(1.0, 0.0, 0.0); 2]);
+    // Palettes without a name are named after their number
+    assert_eq!(palettes[1].name, "3");
+    assert_eq!(palettes[1].colors, vec![(0.0, 1.0, 0.0)]);
+}
+
+fn assert_close(color: Color, expected: Color) {
+    let close = |a: f32, b: f32| (a - b).abs() < 0.02;
+    assert!(
+        close(color.0, expected.0) && close(color.1, expected.1) && close(color.2, expected.2),
+        "{:?} instead of {:?}",
+        color,
+        expected
+    );
+}
+
+/// Two gradients as Apophysis writes them, with titles holding spaces and braces
+const UGR: &str = r#"Fire_and_Ice {
+gradient:
+ title="Fire and Ice" smooth=no
+ index=0 color=255
+ index=200 color=16711680
+ index=399 color=65280
+}
+
+Braces {
+gradient:
+ title="Braces {and} more" smooth=no
+ index=0 color=0
+ index=399 color=16777215
+}
+"#;
+
+#[test]
+fn ugr_gradients_are_read() {
+    let palettes = palette::parse_ugr(UGR).unwrap();
+    assert_eq!(palettes.len(), 2);
+    assert_eq!(palettes[0].name, "Fire and Ice");
+    assert_eq!(palettes[1].name, "Braces {and} more");
+    let colors = &palettes[0].colors;
+    assert_eq!(colors.len(), PALETTE_SIZE);
+    // Colors are r + 256 g + 65536 b, at indices in [0, 400)
+    assert_eq!(colors[0], (1.0, 0.0, 0.0));
+    assert_close(colors[128], (0.0, 0.0, 1.0));
+    assert_eq!(colors[255], (0.0, 1.0, 0.0));
+    assert_eq!(palettes[1].colors[0], (0.0, 0.0, 0.0));
+    assert_eq!(palettes[1].colors[255], (1.0, 1.0, 1.0));
+
+    // Without a title, a gradient is named after its block
+    let untitled = palette::parse_ugr("plain {\ngradient:\n index=0 color=0\n}").unwrap();
+    assert_eq!(untitled[0].name, "plain");
+}
+
+#[test]
+fn malformed_ugr_gradients_are_rejected() {
+    let invalid = [
+        "a {\ngradient:\n title=\"unterminated index=0 color=0\n}",
+        "a {\ngradient:\n title=\"no colors\"\n}",
+        "a {\ngradient:\n color=255\n}",
+        "a {\ngradient:\n index=zero color=255\n}",
+        "a {\ngradient:\n index=0 color=red\n}",
+        "a {\ngradient:\n index=0 color=255\n",
+    ];
+    for text in invalid {
+        assert!(
+            matches!(palette::parse_ugr(text), Err(palette::Error::Invalid(_))),
+            "{:?}",
+            text
+        );
+    }
+    assert_eq!(palette::parse_ugr(""), Err(palette::Error::NoPalette));
+}
+
+#[test]
+fn map_files_are_read() {
+    // A full Fractint map keeps its colors, with comments after them
+    let text: String = (0..256)
+        .map(|idx| format!("{:3} {:3} 0 ; entry {}\n", idx, 255 - idx, idx))
+        .collect();
+    let colors = palette::parse_map(&text).unwrap();
+    assert_eq!(colors.len(), PALETTE_SIZE);
+    for (idx, &color) in colors.iter().enumerate() {
+        let channel = idx as f32 / 255.0;
+        assert_close(color, (channel, 1.0 - channel, 0.0));
+    }
+
+    // Shorter maps are spread over the whole palette
+    let colors = palette::parse_map("  0   0   0\n\n255 0 0\n  0 0 255\n").unwrap();
+    assert_eq!(colors.len(), PALETTE_SIZE);
+    assert_eq!(colors[0], (0.0, 0.0, 0.0));
+    assert_close(colors[128], (1.0, 0.0, 0.0));
+    assert_eq!(colors[255], (0.0, 0.0, 1.0));
+}
+
+#[test]
+fn malformed_map_files_are_rejected() {
+    for text in ["0 0 0\n255 0\n", "0 0 0\nred green blue\n"] {
+        assert!(
+            matches!(palette::parse_map(text), Err(palette::Error::Invalid(_))),
+            "{:?}",
+            text
+        );
+    }
+    assert_eq!(palette::parse_map("\n \n"), Err(palette::Error::NoPalette));
+}
+
+/// GIMP gradient going from red to yellow and from yellow to blue, as GIMP writes it
+const GGR: &str = "GIMP Gradient
+Name: Fire and Ice
+2
+0.000000 0.250000 0.500000 1.000000 0.000000 0.000000 1.000000 1.000000 1.000000 0.000000 \
+1.000000 0 0
+0.500000 0.750000 1.000000 1.000000 1.000000 0.000000 1.000000 0.000000 0.000000 1.000000 \
+1.000000 0 0
+";
+
+#[test]
+fn ggr_gradients_are_read() {
+    let palette = palette::parse_ggr(GGR).unwrap();
+    assert_eq!(palette.name, "Fire and Ice");
+    assert_eq!(palette.colors.len(), PALETTE_SIZE);
+    assert_close(palette.colors[0], (1.0, 0.0, 0.0));
+    assert_close(palette.colors[64], (1.0, 0.5, 0.0));
+    assert_close(palette.colors[128], (1.0, 1.0, 0.0));
+    assert_close(palette.colors[255], (0.0, 0.0, 1.0));
+}
+
+#[test]
+fn malformed_ggr_gradients_are_rejected() {
+    let segment = "0 0.5 1 0 0 0 1 1 1 1 1 0 0";
+    let cases = [
+        (format!("Name: x\n1\n{}", segment), "header"),
+        (format!("GIMP Gradient\nName: x\none\n{}", segment), "count"),
+        (format!("GIMP Gradient\n1\n{}", &segment[..10]), "segment"),
+        (format!("GIMP Gradient\n1\n{} x", &segment[..24]), "segment"),
+    ];
+    for (text, what) in &cases {
+        assert!(
+            matches!(palette::parse_ggr(text), Err(palette::Error::Invalid(_))),
+            "{}: {:?}",
+            what,
+            text
+        );
+    }
+    let missing = format!("GIMP Gradient\n2\n{}", segment);
+    assert_eq!(palette::parse_ggr(&missing), Err(palette::Error::NoPalette));
+}
+
+const RED: [u8; 3] = [255, 0, 0];
+const BLUE: [u8; 3] = [0, 0, 255];
+
+/// Picture whose left half is red and right half is blue, with the pixels of `extra` added
+/// after them, as samples of `channels` channels
+fn two_colors(channels: usize, extra: &[u8]) -> Vec<u8> {
+    let mut pixels = Vec::new();
+    for idx in 0..64 {
+        pixels.extend_from_slice(if idx % 8 < 4 { &RED } else { &BLUE });
+        if channels == 4 {
+            pixels.push(255);
+        }
+    }
+    pixels.extend_from_slice(extra);
+    pixels
+}
+
+#[test]
+fn pictures_give_their_colors() {
+    let colors = palette::from_pixels(&two_colors(3, &[]), 3).unwrap();
+    assert_eq!(colors.len(), PALETTE_SIZE);
+    assert!(colors.contains(&(1.0, 0.0, 0.0)));
+    assert!(colors.contains(&(0.0, 0.0, 1.0)));
+    // The clusters are chained from the darkest one
+    assert_eq!(colors[0], (0.0, 0.0, 1.0));
+    assert_eq!(colors[255], (1.0, 0.0, 0.0));
+}
+
+#[test]
+fn transparent_pixels_are_left_out() {
+    // The same picture with opaque alpha, plus green pixels that are transparent
+    let green = [0, 255, 0, 0].repeat(32);
+    let rgba = palette::from_pixels(&two_colors(4, &green), 4).unwrap();
+    assert_eq!(rgba, palette::from_pixels(&two_colors(3, &[]), 3).unwrap());
+    assert!(rgba.iter().all(|&(_, green, _)| green == 0.0));
+
+    // With 3 channels, the same bytes are other pixels
+    let rgb = palette::from_pixels(&two_colors(4, &green), 3).unwrap();
+    assert_ne!(rgb, rgba);
+
+    for channels in [0, 1, 2, 5] {
+        assert!(matches!(
+            palette::from_pixels(&two_colors(3, &[]), channels),
+            Err(palette::Error::Image(_))
+        ));
+    }
+    assert!(matches!(
+        palette::from_pixels(&green, 4),
+        Err(palette::Error::Image(_))
+    ));
+}
+
+/// Encodes 8x8 pixels as a PNG of `color_type`
+fn png(pixels: &[u8], color_type: png::ColorType) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, 8, 8);
+    encoder.set_color(color_type);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(pixels).unwrap();
+    writer.finish().unwrap();
+    data
+}
+
+#[test]
+fn png_pictures_give_their_colors() {
+    let expected = palette::from_pixels(&two_colors(3, &[]), 3).unwrap();
+    let rgb = png(&two_colors(3, &[]), png::ColorType::Rgb);
+    assert_eq!(palette::from_png(&rgb).unwrap(), expected);
+    let rgba = png(&two_colors(4, &[]), png::ColorType::Rgba);
+    assert_eq!(palette::from_png(&rgba).unwrap(), expected);
+
+    // Gray pictures give gray palettes
+    let gray: Vec<u8> = (0..64).map(|idx| if idx < 32 { 0 } else { 255 }).collect();
+    let colors = palette::from_png(&png(&gray, png::ColorType::Grayscale)).unwrap();
+    assert_eq!(colors[0], (0.0, 0.0, 0.0));
+    assert_eq!(colors[255], (1.0, 1.0, 1.0));
+
+    assert!(matches!(
+        palette::from_png(b"not a png"),
+        Err(palette::Error::Image(_))
+    ));
+}
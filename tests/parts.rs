@@ -0,0 +1,100 @@
+//! Checks that the partial renders of Web Workers, once merged, give the picture rendered at once
+
+use fractal_flames::{PartError, Picture, RenderOptions};
+
+const SEED: u64 = 31;
+
+fn options() -> RenderOptions {
+    RenderOptions::builder()
+        .size(48, 32)
+        .iterations(30_000)
+        .supersample(2)
+        .build()
+        .unwrap()
+}
+
+fn picture() -> Picture {
+    Picture::with_options(options()).unwrap()
+}
+
+#[test]
+fn merged_parts_give_the_whole_picture() {
+    let mut expected = picture();
+    expected.paint_with_seed(SEED);
+    assert!(expected.hits().iter().any(|&hits| hits > 0));
+
+    for workers in [1, 3, 5] {
+        let parts: Vec<Vec<u8>> = (0..workers)
+            .map(|worker| picture().render_part(SEED, worker, workers))
+            .collect();
+        // In order, and in the order the workers could finish in
+        let orders = [
+            (0..workers as usize).collect::<Vec<_>>(),
+            (0..workers as usize).rev().collect(),
+            // Every other worker first, the numbers of workers are odd
+            (0..workers)
+                .map(|worker| (worker * 2 % workers) as usize)
+                .collect(),
+        ];
+        for order in orders {
+            let mut merged = picture();
+            for &worker in &order {
+                merged.merge_part(&parts[worker]).unwrap();
+            }
+            merged.finish_parts(SEED);
+            assert_eq!(merged.genome(), expected.genome());
+            assert_eq!(merged.stats(), expected.stats(), "{} workers", workers);
+            assert_eq!(merged.hits(), expected.hits(), "{} workers", workers);
+            assert_eq!(merged.colors(), expected.colors(), "{} workers", workers);
+            assert_eq!(
+                merged.to_png(true, true),
+                expected.to_png(true, true),
+                "{} workers, merged in order {:?}",
+                workers,
+                order
+            );
+        }
+    }
+}
+
+#[test]
+fn parts_of_another_size_are_rejected() {
+    let mut picture = picture();
+    let expected = picture.render_part(SEED, 0, 2).len();
+
+    let small = RenderOptions::builder()
+        .size(32, 32)
+        .iterations(1_000)
+        .build()
+        .unwrap();
+    let part = Picture::with_options(small)
+        .unwrap()
+        .render_part(SEED, 0, 2);
+    assert_eq!(
+        picture.merge_part(&part),
+        Err(PartError {
+            expected,
+            found: part.len(),
+        })
+    );
+
+    // A part cut short, or with extra bytes, is rejected too
+    let part = picture.render_part(SEED, 1, 2);
+    for bad in [&part[..part.len() - 1], &[part.as_slice(), &[0]].concat()] {
+        assert_eq!(
+            picture.merge_part(bad),
+            Err(PartError {
+                expected,
+                found: bad.len(),
+            })
+        );
+    }
+
+    // Rejected parts are not merged
+    picture.merge_part(&part).unwrap();
+    picture.finish_parts(SEED);
+    let mut only_part = self::picture();
+    only_part.merge_part(&part).unwrap();
+    only_part.finish_parts(SEED);
+    assert_eq!(picture.hits(), only_part.hits());
+}
@@ -0,0 +1,83 @@
+//! Checks that drawing a flame progressively gives the picture rendered at once
+
+use fractal_flames::{Picture, RenderOptions};
+
+const SEED: u64 = 12;
+
+fn options() -> RenderOptions {
+    RenderOptions::builder()
+        .size(40, 30)
+        .iterations(25_000)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn iterations_in_steps_give_the_whole_picture() {
+    let mut expected = Picture::with_options(options()).unwrap();
+    expected.paint_with_seed(SEED);
+
+    // Steps that do not divide the iterations, nor share evenly between the walkers
+    for steps in [[25_000, 0, 0, 0], [1, 63, 10_000, 14_936], [7_000; 4]] {
+        let mut picture = Picture::with_options(options()).unwrap();
+        picture.start_with_seed(SEED);
+        for iterations in steps {
+            picture.iterate(iterations.min(25_000 - picture.iterations_done()));
+            // Previews can be drawn along the way without changing the result
+            picture.tone_map();
+        }
+        assert_eq!(picture.iterations_done(), 25_000);
+        assert_eq!(picture.stats(), expected.stats(), "{:?}", steps);
+        assert_eq!(picture.hits(), expected.hits(), "{:?}", steps);
+        assert_eq!(picture.colors(), expected.colors(), "{:?}", steps);
+        assert_eq!(picture.to_png(true, true), expected.to_png(true, true));
+    }
+}
+
+#[test]
+fn tone_mapping_changes_without_iterating_again() {
+    let mut picture = Picture::with_options(options()).unwrap();
+    picture.paint_with_seed(SEED);
+    let first = picture.colors().to_vec();
+
+    picture.set_gamma(1.5).unwrap();
+    assert_eq!(picture.iterations_done(), 25_000);
+    let mut expected =
+        Picture::with_options(options().to_builder().gamma(1.5).build().unwrap()).unwrap();
+    expected.paint_with_seed(SEED);
+    assert_ne!(picture.colors(), first.as_slice());
+    assert_eq!(picture.options(), expected.options());
+    assert_eq!(picture.colors(), expected.colors());
+    assert_eq!(picture.to_png(true, true), expected.to_png(true, true));
+
+    // Only the tone mapping settings are taken, the size and iterations of the picture stay
+    let tone_mapping = options()
+        .to_builder()
+        .size(10, 10)
+        .iterations(1)
+        .brightness(2.5)
+        .contrast(1.2)
+        .vibrancy(0.5)
+        .estimator_radius(3.0)
+        .build()
+        .unwrap();
+    picture.set_tone_mapping(&tone_mapping).unwrap();
+    assert_eq!(picture.hits(), expected.hits());
+    let mut expected = Picture::with_options(
+        options()
+            .to_builder()
+            .brightness(2.5)
+            .contrast(1.2)
+            .vibrancy(0.5)
+            .estimator_radius(3.0)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+    expected.paint_with_seed(SEED);
+    assert_eq!(picture.options(), expected.options());
+    assert_eq!(picture.colors(), expected.colors());
+    // An invalid gamma leaves the settings unchanged
+    assert!(picture.set_gamma(-1.0).is_err());
+    assert_eq!(picture.options(), expected.options());
+}
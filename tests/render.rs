@@ -0,0 +1,171 @@
+//! Checks that walkers recover from points that are not finite anymore, and that the number of
+//! threads never changes the picture drawn from a seed
+
+use fractal_flames::{
+    create_variation, Genome, Pcg32, Picture, RenderOptions, Xform, PALETTE_SIZE,
+};
+
+/// Flame whose first xform sends every point to the origin, where the spherical variation of the
+/// second one divides by zero
+fn singular_flame() -> Genome {
+    let xform = |coeffs_pre, variation| Xform {
+        weight: 1.0,
+        coeffs_pre,
+        coeffs_post: (1.0, 0.0, 0.0, 0.0, 1.0, 0.0),
+        variations: vec![(create_variation(variation).unwrap(), 1.0)],
+        color: 0.5,
+        color_speed: 0.5,
+    };
+    Genome {
+        xforms: vec![
+            xform((0.0, 0.0, 0.0, 0.0, 0.0, 0.0), "linear"),
+            xform((1.0, 0.0, 0.0, 0.0, 1.0, 0.0), "spherical"),
+        ],
+        final_xform: None,
+        palette: vec![(1.0, 1.0, 1.0); PALETTE_SIZE],
+    }
+}
+
+/// Flame whose only xform draws every point towards the origin, and whose final xform moves the
+/// drawn points by half a unit
+fn final_xform_flame() -> Genome {
+    let xform = |coeffs_pre, coeffs_post| Xform {
+        weight: 1.0,
+        coeffs_pre,
+        coeffs_post,
+        variations: vec![(create_variation("linear").unwrap(), 1.0)],
+        color: 0.5,
+        color_speed: 0.5,
+    };
+    let identity = (1.0, 0.0, 0.0, 0.0, 1.0, 0.0);
+    Genome {
+        xforms: vec![xform((0.5, 0.0, 0.0, 0.0, 0.5, 0.0), identity)],
+        final_xform: Some(xform(identity, (1.0, 0.0, 0.5, 0.0, 1.0, 0.0))),
+        palette: vec![(1.0, 1.0, 1.0); PALETTE_SIZE],
+    }
+}
+
+fn options() -> RenderOptions {
+    RenderOptions::builder()
+        .size(64, 64)
+        .iterations(20_000)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn walkers_recover_from_bad_values() {
+    let mut picture = Picture::with_options(options()).unwrap();
+    picture.render(&singular_flame(), &mut Pcg32::new(1));
+    let stats = picture.stats();
+    assert_eq!(stats.iterations, 20_000);
+    assert!(stats.bad_values > 0);
+    // A quarter of the iterations divide by zero, the others keep being drawn
+    let hits: u64 = picture.hits().iter().sum();
+    assert!(hits > 20_000 / 2, "only {} hits", hits);
+}
+
+#[test]
+fn final_xform_is_only_drawn() {
+    let mut picture = Picture::with_options(options()).unwrap();
+    picture.render(&final_xform_flame(), &mut Pcg32::new(1));
+    // The walkers stay at the origin, so every point drawn is the origin moved by the final
+    // xform. Feeding the final xform back into the chaos game would draw (1, 0) instead.
+    let moved = Genome {
+        xforms: vec![Xform {
+            coeffs_pre: (0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+            coeffs_post: (1.0, 0.0, 0.5, 0.0, 1.0, 0.0),
+            ..final_xform_flame().xforms[0].clone()
+        }],
+        final_xform: None,
+        ..final_xform_flame()
+    };
+    let mut expected = Picture::with_options(options()).unwrap();
+    expected.render(&moved, &mut Pcg32::new(1));
+    assert_eq!(picture.stats(), expected.stats());
+    let (x, y) = centroid(picture.hits(), 64);
+    let (expected_x, expected_y) = centroid(expected.hits(), 64);
+    assert!(
+        (x - expected_x).abs() < 1.0 && (y - expected_y).abs() < 1.0,
+        "drawn around ({}, {}) instead of ({}, {})",
+        x,
+        y,
+        expected_x,
+        expected_y
+    );
+}
+
+/// Returns the mean position, in pixels, of the hits of a picture `width` pixels wide
+fn centroid(hits: &[u64], width: usize) -> (f64, f64) {
+    let total = hits.iter().sum::<u64>() as f64;
+    assert!(total > 0.0);
+    hits.iter()
+        .enumerate()
+        .fold((0.0, 0.0), |(x, y), (idx, &hits)| {
+            let weight = hits as f64 / total;
+            (
+                x + (idx % width) as f64 * weight,
+                y + (idx / width) as f64 * weight,
+            )
+        })
+}
+
+#[test]
+fn bad_values_do_not_depend_on_threads() {
+    let mut picture = Picture::with_options(options()).unwrap();
+    picture.render(&singular_flame(), &mut Pcg32::new(1));
+    let mut threaded = Picture::with_options(options()).unwrap();
+    threaded.render_with_threads(&singular_flame(), &mut Pcg32::new(1), 3);
+    assert_eq!(picture.stats(), threaded.stats());
+    assert_eq!(picture.hits(), threaded.hits());
+}
+
+#[test]
+fn random_flames_do_not_depend_on_threads() {
+    let options = RenderOptions::builder()
+        .size(96, 64)
+        .iterations(200_000)
+        .supersample(2)
+        .build()
+        .unwrap();
+    for seed in [7, 42] {
+        let mut picture = Picture::with_options(options.clone()).unwrap();
+        picture.paint_with_seed(seed);
+        assert!(picture.hits().iter().any(|&hits| hits > 0));
+        for threads in [1, 3, 8] {
+            let mut threaded = Picture::with_options(options.clone()).unwrap();
+            threaded.start_with_seed(seed);
+            threaded.iterate_with_threads(options.iterations, threads);
+            threaded.tone_map();
+            assert_eq!(picture.genome(), threaded.genome());
+            assert_eq!(picture.stats(), threaded.stats(), "{} threads", threads);
+            assert_eq!(picture.hits(), threaded.hits(), "{} threads", threads);
+            assert_eq!(picture.colors(), threaded.colors(), "{} threads", threads);
+        }
+    }
+}
+
+/// Hash of the hits of the flame of seed 2024
+const RECORDED_HASH: u64 = 2_979_884_956_656_443_589;
+
+/// FNV-1a hash of the hits of a picture
+fn hash(hits: &[u64]) -> u64 {
+    hits.iter()
+        .flat_map(|hits| hits.to_le_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+#[test]
+fn same_seed_gives_same_bytes() {
+    let mut first = Picture::with_options(options()).unwrap();
+    first.paint_with_seed(2024);
+    let mut second = Picture::with_options(options()).unwrap();
+    second.paint_with_seed(2024);
+    assert!(first.hits().iter().sum::<u64>() > 0);
+    assert_eq!(first.hits(), second.hits());
+    assert_eq!(first.to_png(true, true), second.to_png(true, true));
+    // Recorded hits of the seed, which only change when the flames of a seed change on purpose
+    assert_eq!(hash(first.hits()), RECORDED_HASH);
+}
@@ -0,0 +1,437 @@
+//! Checks every variation of the registry against reference formulas
+//!
+//! The references are written from flam3 (`variations.c`), which the paper's appendix was taken
+//! from and which every flam3 file is rendered with, in double precision and without going
+//! through the crate's precomputed values. With the paper's convention, θ is the angle arctan(x/y)
+//! (`atan2(x, y)`, so sin θ = x / r and cos θ = y / r), and φ is the angle `atan2(y, x)` that flam3
+//! uses for julia, julian, juliascope, radial_blur and ngon.
+
+use fractal_flames::{
+    create_variation, Coeffs, Pcg32, Point, Rng, Variation, Xform, FLAM3_VARIATIONS, VARIATIONS,
+};
+use std::f64::consts::PI;
+
+const EPS: f64 = 1e-10;
+
+const IDENTITY: Coeffs = (1.0, 0.0, 0.0, 0.0, 1.0, 0.0);
+
+/// Affine transformations applied before the variations, with translations so that the
+/// variations reading the coefficients (waves, popcorn, rings and fan) get non trivial values
+const COEFFS: &[Coeffs] = &[
+    (0.9, -0.3, 0.4, 0.2, 1.1, -0.6),
+    (-0.7, 0.5, -0.25, 0.6, 0.8, 0.35),
+];
+
+const WEIGHTS: &[f32] = &[1.0, 0.6];
+
+/// Random numbers drawn by the random variations, all multiples of 1/64 so that they are exact in
+/// single and double precision
+const DRAWS: &[f32] = &[
+    0.203125, 0.765625, 0.421875, 0.953125, 0.046875, 0.578125, 0.328125, 0.890625,
+];
+
+/// Generator replaying `DRAWS` from a given position
+struct Replay(usize);
+
+impl Replay {
+    fn next(&mut self) -> f32 {
+        let value = DRAWS[self.0 % DRAWS.len()];
+        self.0 += 1;
+        value
+    }
+}
+
+impl Rng for Replay {
+    fn next_u32(&mut self) -> u32 {
+        ((self.next() * (1 << 24) as f32) as u32) << 8
+    }
+}
+
+/// Point on which a variation is evaluated, once the affine transformation is applied
+struct Input {
+    x: f64,
+    y: f64,
+    coeffs: [f64; 6],
+    weight: f64,
+}
+
+/// Result of the variation `name` with parameters `param`, drawing its random numbers from
+/// `rand`, scaled by the weight
+fn reference(
+    name: &str,
+    input: &Input,
+    param: &dyn Fn(&str) -> f64,
+    rand: &mut dyn FnMut() -> f64,
+) -> (f64, f64) {
+    let Input { x, y, weight, .. } = *input;
+    let [_, b, c, _, e, f] = input.coeffs;
+    let r2 = x * x + y * y;
+    let r = r2.sqrt();
+    let theta = x.atan2(y);
+    let phi = y.atan2(x);
+    let (sina, cosa) = (x / r, y / r);
+    let (px, py) = match name {
+        "linear" => (x, y),
+        "sinusoidal" => (x.sin(), y.sin()),
+        "spherical" => (x / (r2 + EPS), y / (r2 + EPS)),
+        "swirl" => (x * r2.sin() - y * r2.cos(), x * r2.cos() + y * r2.sin()),
+        "horseshoe" => ((x - y) * (x + y) / (r + EPS), 2.0 * x * y / (r + EPS)),
+        "polar" => (theta / PI, r - 1.0),
+        "handkerchief" => (r * (theta + r).sin(), r * (theta - r).cos()),
+        "heart" => (r * (r * theta).sin(), -r * (r * theta).cos()),
+        "disc" => {
+            let a = theta / PI;
+            (a * (PI * r).sin(), a * (PI * r).cos())
+        }
+        "spiral" => {
+            let r = r + EPS;
+            ((cosa + r.sin()) / r, (sina - r.cos()) / r)
+        }
+        "hyperbolic" => (sina / (r + EPS), cosa * (r + EPS)),
+        "diamond" => (sina * r.cos(), cosa * r.sin()),
+        "ex" => {
+            let n0 = (theta + r).sin();
+            let n1 = (theta - r).cos();
+            let m0 = n0 * n0 * n0 * r;
+            let m1 = n1 * n1 * n1 * r;
+            (m0 + m1, m0 - m1)
+        }
+        "julia" => {
+            let mut a = 0.5 * phi;
+            if rand() >= 0.5 {
+                a += PI;
+            }
+            (r.sqrt() * a.cos(), r.sqrt() * a.sin())
+        }
+        "bent" => (
+            if x < 0.0 { 2.0 * x } else { x },
+            if y < 0.0 { y / 2.0 } else { y },
+        ),
+        "waves" => (
+            x + b * (y / (c * c + EPS)).sin(),
+            y + e * (x / (f * f + EPS)).sin(),
+        ),
+        "fisheye" => (2.0 / (r + 1.0) * y, 2.0 / (r + 1.0) * x),
+        "popcorn" => (x + c * (3.0 * y).tan().sin(), y + f * (3.0 * x).tan().sin()),
+        "exponential" => {
+            let d = (x - 1.0).exp();
+            (d * (PI * y).cos(), d * (PI * y).sin())
+        }
+        "power" => {
+            let d = r.powf(sina);
+            (d * cosa, d * sina)
+        }
+        "cosine" => ((PI * x).cos() * y.cosh(), -(PI * x).sin() * y.sinh()),
+        "rings" => {
+            let dx = c * c + EPS;
+            let d = (r + dx) % (2.0 * dx) - dx + r * (1.0 - dx);
+            (d * cosa, d * sina)
+        }
+        "fan" => {
+            let dx = PI * (c * c + EPS);
+            let a = if (theta + f) % dx > dx / 2.0 {
+                theta - dx / 2.0
+            } else {
+                theta + dx / 2.0
+            };
+            (r * a.cos(), r * a.sin())
+        }
+        "blob" => {
+            let (low, high) = (param("blob_low"), param("blob_high"));
+            let waves = param("blob_waves");
+            let d = r * (low + (high - low) * (0.5 + 0.5 * (waves * theta).sin()));
+            (d * sina, d * cosa)
+        }
+        "pdj" => (
+            (param("pdj_a") * y).sin() - (param("pdj_b") * x).cos(),
+            (param("pdj_c") * x).sin() - (param("pdj_d") * y).cos(),
+        ),
+        "fan2" => {
+            let dx = PI * (param("fan2_x").powi(2) + EPS);
+            let dy = param("fan2_y");
+            let t = theta + dy - dx * ((theta + dy) / dx).trunc();
+            let a = if t > dx / 2.0 {
+                theta - dx / 2.0
+            } else {
+                theta + dx / 2.0
+            };
+            (r * a.sin(), r * a.cos())
+        }
+        "rings2" => {
+            let dx = param("rings2_val").powi(2) + EPS;
+            let d = r - 2.0 * dx * ((r + dx) / (2.0 * dx)).trunc() + r * (1.0 - dx);
+            (d * sina, d * cosa)
+        }
+        "eyefish" => (2.0 / (r + 1.0) * x, 2.0 / (r + 1.0) * y),
+        "bubble" => (x / (0.25 * r2 + 1.0), y / (0.25 * r2 + 1.0)),
+        "cylinder" => (x.sin(), y),
+        "perspective" => {
+            let (angle, dist) = (param("perspective_angle"), param("perspective_dist"));
+            let t = 1.0 / (dist - y * (angle * PI / 2.0).sin());
+            (dist * x * t, dist * (angle * PI / 2.0).cos() * y * t)
+        }
+        "noise" => {
+            let a = 2.0 * PI * rand();
+            let d = rand();
+            (x * d * a.cos(), y * d * a.sin())
+        }
+        "julian" | "juliascope" => {
+            let power = param(&format!("{}_power", name));
+            let dist = param(&format!("{}_dist", name));
+            let root = (power.abs() * rand()).trunc();
+            let a = if name == "juliascope" && root % 2.0 != 0.0 {
+                (2.0 * PI * root - phi) / power
+            } else {
+                (2.0 * PI * root + phi) / power
+            };
+            let d = r2.powf(dist / power / 2.0);
+            (d * a.cos(), d * a.sin())
+        }
+        "blur" => {
+            let a = 2.0 * PI * rand();
+            let d = rand();
+            (d * a.cos(), d * a.sin())
+        }
+        "gaussian_blur" => {
+            let a = 2.0 * PI * rand();
+            let d = rand() + rand() + rand() + rand() - 2.0;
+            (d * a.cos(), d * a.sin())
+        }
+        "radial_blur" => {
+            // Not scaled by the weight, which sets the amount of blur instead
+            let angle = param("radial_blur_angle") * PI / 2.0;
+            let g = weight * (rand() + rand() + rand() + rand() - 2.0);
+            let a = phi + angle.sin() * g;
+            let rz = angle.cos() * g - 1.0;
+            return (r * a.cos() + rz * x, r * a.sin() + rz * y);
+        }
+        "pie" => {
+            let slices = param("pie_slices");
+            let slice = (rand() * slices + 0.5).trunc();
+            let a = param("pie_rotation")
+                + 2.0 * PI * (slice + rand() * param("pie_thickness")) / slices;
+            let d = rand();
+            (d * a.cos(), d * a.sin())
+        }
+        "ngon" => {
+            let r_factor = r2.powf(param("ngon_power") / 2.0);
+            let b = 2.0 * PI / param("ngon_sides");
+            let mut a = phi - b * (phi / b).floor();
+            if a > b / 2.0 {
+                a -= b;
+            }
+            let amp = param("ngon_corners") * (1.0 / (a.cos() + EPS) - 1.0) + param("ngon_circle");
+            let amp = amp / (r_factor + EPS);
+            (amp * x, amp * y)
+        }
+        "curl" => {
+            let (c1, c2) = (param("curl_c1"), param("curl_c2"));
+            let re = 1.0 + c1 * x + c2 * (x * x - y * y);
+            let im = c1 * y + 2.0 * c2 * x * y;
+            let d = 1.0 / (re * re + im * im);
+            ((x * re + y * im) * d, (y * re - x * im) * d)
+        }
+        "rectangles" => {
+            let fold = |t: f64, size: f64| {
+                if size == 0.0 {
+                    t
+                } else {
+                    (2.0 * (t / size).floor() + 1.0) * size - t
+                }
+            };
+            (
+                fold(x, param("rectangles_x")),
+                fold(y, param("rectangles_y")),
+            )
+        }
+        "arch" => {
+            let a = rand() * weight * PI;
+            return (weight * a.sin(), weight * a.sin() * a.sin() / a.cos());
+        }
+        "tangent" => (x.sin() / y.cos(), y.tan()),
+        "square" => (rand() - 0.5, rand() - 0.5),
+        "rays" => {
+            let a = weight * rand() * PI;
+            let d = weight * a.tan() * weight / (r2 + EPS);
+            return (d * x.cos(), d * y.sin());
+        }
+        "blade" => {
+            let d = rand() * weight * r;
+            let w = weight * x;
+            return (w * (d.cos() + d.sin()), w * (d.cos() - d.sin()));
+        }
+        "secant2" => {
+            let cr = (weight * r).cos();
+            let d = if cr < 0.0 {
+                1.0 / cr + 1.0
+            } else {
+                1.0 / cr - 1.0
+            };
+            return (weight * x, weight * d);
+        }
+        "twintrian" => {
+            let d = rand() * weight * r;
+            let mut diff = (d.sin() * d.sin()).log10() + d.cos();
+            if !diff.is_finite() {
+                diff = -30.0;
+            }
+            let w = weight * x;
+            return (w * diff, w * (diff - d.sin() * PI));
+        }
+        "cross" => {
+            let s = x * x - y * y;
+            let d = (1.0 / (s * s + EPS)).sqrt();
+            (d * x, d * y)
+        }
+        _ => panic!("no reference for variation `{}`", name),
+    };
+    (weight * px, weight * py)
+}
+
+/// Points of the grid, none of them on an axis
+fn grid() -> impl Iterator<Item = (f32, f32)> {
+    (0..13).flat_map(|i| (0..13).map(move |j| (-1.9 + 0.31 * i as f32, -1.87 + 0.31 * j as f32)))
+}
+
+/// Default parameters of `name`, and parameters drawn at random
+fn parameter_sets(name: &str) -> Vec<Box<dyn Variation>> {
+    let variation = create_variation(name).unwrap();
+    let mut sets = vec![variation.clone()];
+    if !variation.params().is_empty() {
+        for seed in 0..4 {
+            let mut random = variation.clone();
+            random.randomize(&mut Pcg32::new(seed));
+            sets.push(random);
+        }
+    }
+    sets
+}
+
+#[test]
+fn variations_match_the_reference_formulas() {
+    let mut failures = Vec::new();
+    for info in VARIATIONS {
+        for variation in parameter_sets(info.name) {
+            let param = |name: &str| variation.param(name).unwrap() as f64;
+            for &coeffs in COEFFS {
+                for &weight in WEIGHTS {
+                    for (n, (x, y)) in grid().enumerate() {
+                        let xform = Xform {
+                            weight: 1.0,
+                            coeffs_pre: coeffs,
+                            coeffs_post: IDENTITY,
+                            variations: vec![(variation.clone(), weight)],
+                            color: 0.0,
+                            color_speed: 0.0,
+                        };
+                        let Point(got_x, got_y) = xform.apply(Point(x, y), &mut Replay(n));
+
+                        // Same point as the one given to the variation
+                        let Point(x, y) = Point(x, y).affine(coeffs);
+                        let (a, b, c, d, e, f) = coeffs;
+                        let input = Input {
+                            x: x as f64,
+                            y: y as f64,
+                            coeffs: [a, b, c, d, e, f].map(f64::from),
+                            weight: weight as f64,
+                        };
+                        let evaluate = |input: &Input| {
+                            let mut replay = Replay(n);
+                            let mut rand = || replay.next() as f64;
+                            reference(info.name, input, &param, &mut rand)
+                        };
+                        let (want_x, want_y) = evaluate(&input);
+
+                        // Points sent far away, where single precision loses the details anyway,
+                        // are not compared, nor points where the formula is so ill-conditioned
+                        // (close to a pole or a discontinuity) that a rounding error changes its
+                        // result
+                        if !(want_x.abs() < 1e3 && want_y.abs() < 1e3) {
+                            continue;
+                        }
+                        let close = |(x, y): (f32, f32), (want_x, want_y): (f64, f64)| {
+                            let close = |got: f64, want: f64| {
+                                (got - want).abs() <= 1e-3 * (1.0 + want.abs())
+                            };
+                            close(x as f64, want_x) && close(y as f64, want_y)
+                        };
+                        let nudged = evaluate(&Input {
+                            x: input.x * (1.0 + 1e-6),
+                            y: input.y * (1.0 + 1e-6),
+                            ..input
+                        });
+                        if !close((nudged.0 as f32, nudged.1 as f32), (want_x, want_y)) {
+                            continue;
+                        }
+                        if !close((got_x, got_y), (want_x, want_y)) {
+                            failures.push(format!(
+                                "{} {:?} at ({}, {}) weight {}: got ({}, {}), expected ({}, {})",
+                                info.name,
+                                variation,
+                                input.x,
+                                input.y,
+                                weight,
+                                got_x,
+                                got_y,
+                                want_x,
+                                want_y
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} mismatches:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn registry_follows_flam3_ids() {
+    for info in VARIATIONS {
+        assert_eq!(FLAM3_VARIATIONS[info.id as usize], info.name);
+    }
+    for pair in VARIATIONS.windows(2) {
+        assert!(
+            pair[0].id < pair[1].id,
+            "{} after {}",
+            pair[1].name,
+            pair[0].name
+        );
+    }
+    // Every variation of the paper's appendix, up to cross
+    let cross = FLAM3_VARIATIONS.iter().position(|&name| name == "cross");
+    assert_eq!(cross, Some(48));
+    for name in &FLAM3_VARIATIONS[..=48] {
+        assert!(create_variation(name).is_some(), "{} is missing", name);
+    }
+}
+
+#[test]
+fn random_parameters_stay_in_their_range() {
+    let mut rng = Pcg32::new(7);
+    for info in VARIATIONS {
+        for _ in 0..100 {
+            let mut variation = info.create();
+            variation.randomize(&mut rng);
+            for param in variation.params() {
+                let value = variation.param(param.name).unwrap();
+                assert!(
+                    (param.min..param.max).contains(&value),
+                    "{} = {} out of [{}, {})",
+                    param.name,
+                    value,
+                    param.min,
+                    param.max
+                );
+                if param.integer {
+                    assert_eq!(value, value.floor(), "{} is not an integer", param.name);
+                }
+            }
+        }
+    }
+}
@@ -29,7 +29,7 @@ const getColor = (val) => {
 
 const drawCells = () => {
   // const cell_counter_ptr = picture.cell_counter();
-  // const cell_counter = new Uint32Array(memory.buffer, cell_counter_ptr, width * height);
+  // const cell_counter = new BigUint64Array(memory.buffer, cell_counter_ptr, width * height);
   // const cell_color_ptr = picture.cell_color();
   // const cell_color = new Uint8Array(memory.buffer, cell_color_ptr, 3 * width * height);
   const cell_alpha_ptr = picture.cell_alpha();
@@ -74,6 +74,17 @@ const drawCells = () => {
   ctx.stroke();
 };
 
+const downloadPng = () => {
+  const png = picture.to_png(false, true);
+  const link = document.createElement("a");
+  link.href = URL.createObjectURL(new Blob([png], { type: "image/png" }));
+  link.download = "flame.png";
+  link.click();
+  URL.revokeObjectURL(link.href);
+};
+
+document.getElementById("download-png").addEventListener("click", downloadPng);
+
 // let animationId = null;
 
 // const isPaused = () => {
@@ -107,7 +118,138 @@ const drawCells = () => {
 //   animationId = requestAnimationFrame(renderLoop);
 // }
 
+// Number of Web Workers sharing the iterations, so that the page stays responsive
+const WORKERS = Math.min(navigator.hardwareConcurrency || 4, 8);
+
+const randomSeed = () => crypto.getRandomValues(new BigUint64Array(1))[0];
+
+// Whether a flame is being painted, and whether to paint another one once it is done
+let painting = false;
+let paintAgain = false;
+
+const paintInWorkers = (workers, done) => {
+  const seed = randomSeed();
+  let pending = workers.length;
+  workers.forEach((worker, idx) => {
+    worker.onmessage = ({ data }) => {
+      picture.merge_part(data);
+      pending -= 1;
+      if (pending === 0) {
+        picture.finish_parts(seed);
+        drawCells();
+        done();
+      }
+    };
+    worker.postMessage({ seed, worker: idx, workers: workers.length });
+  });
+};
+
+// Workers that are ready to render, all of them once the first flame is painted
+const workers = [];
+
+const startWorkers = () => {
+  for (let idx = 0; idx < WORKERS; idx++) {
+    const worker = new Worker("worker.js");
+    worker.onmessage = () => {
+      workers.push(worker);
+      if (workers.length === WORKERS) {
+        paint();
+      }
+    };
+  }
+};
+
+// Without workers, draws the flame progressively, a few iterations per animation frame
+const ITERATIONS = 100000n;
+const ITERATIONS_PER_FRAME = 5000n;
+
+const paintProgressively = (done) => {
+  picture.start_with_seed(randomSeed());
+  const step = () => {
+    picture.iterate(ITERATIONS_PER_FRAME);
+    picture.tone_map();
+    drawCells();
+    if (picture.iterations_done() < ITERATIONS) {
+      requestAnimationFrame(step);
+    } else {
+      done();
+    }
+  };
+  requestAnimationFrame(step);
+};
+
+// Palette given to the next flames: the RGBA pixels of a picture, null for random palettes,
+// or undefined to keep the current one
+let nextPalette;
+
+// Gives the next palette to the page and to every worker, otherwise the parts of the workers
+// would be drawn with the palettes of the flames and merged into a mix of palettes. Only done
+// between two flames, so that the parts of a flame all have the same palette.
+const applyPalette = () => {
+  if (nextPalette === undefined) {
+    return;
+  }
+  if (nextPalette === null) {
+    picture.clear_palette();
+  } else {
+    picture.set_palette_from_pixels(nextPalette, 4);
+  }
+  workers.forEach((worker) => worker.postMessage({ palette: nextPalette, channels: 4 }));
+  nextPalette = undefined;
+};
+
+const paint = () => {
+  if (painting) {
+    paintAgain = true;
+    return;
+  }
+  painting = true;
+  applyPalette();
+  const done = () => {
+    painting = false;
+    if (paintAgain) {
+      paintAgain = false;
+      paint();
+    }
+  };
+  if (workers.length === WORKERS) {
+    paintInWorkers(workers, done);
+  } else {
+    paintProgressively(done);
+  }
+};
+
+// Paints a flame with a palette matching the pixels of a picture, or with a random palette
+// again when `pixels` is null. Before the workers are ready, the first flame takes it.
+const setPalette = (pixels) => {
+  nextPalette = pixels;
+  if (typeof Worker === "undefined" || workers.length === WORKERS) {
+    paint();
+  }
+};
+
+const readPixels = async (file) => {
+  const bitmap = await createImageBitmap(file);
+  const canvas = document.createElement("canvas");
+  canvas.width = bitmap.width;
+  canvas.height = bitmap.height;
+  const context = canvas.getContext("2d");
+  context.drawImage(bitmap, 0, 0);
+  return context.getImageData(0, 0, bitmap.width, bitmap.height).data;
+};
+
+document.getElementById("palette-picture").addEventListener("change", async (event) => {
+  const [file] = event.target.files;
+  if (file) {
+    setPalette(await readPixels(file));
+  }
+});
+document.getElementById("random-palette").addEventListener("click", () => setPalette(null));
+
 // drawGrid();
-picture.paint();
-drawCells();
+if (typeof Worker === "undefined") {
+  paint();
+} else {
+  startWorkers();
+}
 // play();
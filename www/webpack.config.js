@@ -1,14 +1,26 @@
 const CopyWebpackPlugin = require("copy-webpack-plugin");
 const path = require('path');
 
-module.exports = {
-  entry: "./bootstrap.js",
-  output: {
-    path: path.resolve(__dirname, "dist"),
-    filename: "bootstrap.js",
+module.exports = [
+  {
+    entry: "./bootstrap.js",
+    output: {
+      path: path.resolve(__dirname, "dist"),
+      filename: "bootstrap.js",
+    },
+    mode: "development",
+    plugins: [
+      new CopyWebpackPlugin(['index.html'])
+    ],
   },
-  mode: "development",
-  plugins: [
-    new CopyWebpackPlugin(['index.html'])
-  ],
-};
+  {
+    entry: "./worker-bootstrap.js",
+    target: "webworker",
+    output: {
+      path: path.resolve(__dirname, "dist"),
+      filename: "worker.js",
+      chunkFilename: "worker.[id].js",
+    },
+    mode: "development",
+  },
+];
@@ -0,0 +1,3 @@
+// Same as `bootstrap.js`, for the Web Workers that render parts of the flames
+import("./worker.js")
+  .catch(e => console.error("Error importing `worker.js`:", e));
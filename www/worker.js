@@ -0,0 +1,23 @@
+import { Picture } from "fractal-flames";
+
+// Renders a share of the iterations of a flame and sends its hits back to the page, which merges
+// the parts of all the workers
+const picture = Picture.new();
+
+self.onmessage = ({ data }) => {
+  // The page sends its palette to every worker, so that all the parts are drawn with it. Messages
+  // are handled in order, so the flames asked for afterwards use the new palette.
+  if ("palette" in data) {
+    if (data.palette === null) {
+      picture.clear_palette();
+    } else {
+      picture.set_palette_from_pixels(data.palette, data.channels);
+    }
+    return;
+  }
+  const { seed, worker, workers } = data;
+  const part = picture.render_part(seed, worker, workers);
+  self.postMessage(part, [part.buffer]);
+};
+
+self.postMessage("ready");